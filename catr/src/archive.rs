@@ -0,0 +1,198 @@
+//! Reading members of tar (optionally gzipped) and zip archives, given as
+//! `bundle.tar.gz:etc/app.conf` or with `--archive-member`.
+//!
+//! Nothing is extracted to disk: a member is streamed from a background
+//! thread in chunks, so large members are not held in memory either.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+use anyhow::{bail, Result};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks the reading thread may get ahead of the output.
+const CHUNKS_AHEAD: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+const EXTENSIONS: [(&str, Kind); 4] = [
+    (".tar.gz", Kind::TarGz),
+    (".tgz", Kind::TarGz),
+    (".tar", Kind::Tar),
+    (".zip", Kind::Zip),
+];
+
+fn kind(archive: &str) -> Result<Kind> {
+    let lower = archive.to_ascii_lowercase();
+    match EXTENSIONS.iter().find(|(ext, _)| lower.ends_with(ext)) {
+        Some(&(_, kind)) => Ok(kind),
+        None => bail!("not a tar or zip archive"),
+    }
+}
+
+/// Splits `bundle.tar.gz:etc/app.conf` into archive and member, unless a
+/// file by that whole name exists.
+pub(crate) fn split_spec(arg: &str) -> Option<(&str, &str)> {
+    if arg == "-" || Path::new(arg).exists() {
+        return None;
+    }
+    let lower = arg.to_ascii_lowercase();
+    let end = EXTENSIONS
+        .iter()
+        .filter_map(|(ext, _)| lower.find(&format!("{}:", ext)).map(|i| i + ext.len()))
+        .min()?;
+    Some((&arg[..end], &arg[end + 1..]))
+}
+
+/// A member name as stored, without the `./` tar likes to put in front.
+fn normalize(name: &str) -> &str {
+    let mut name = name;
+    while let Some(rest) = name.strip_prefix("./") {
+        name = rest;
+    }
+    name
+}
+
+fn tar_reader(archive: &str, kind: Kind) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = BufReader::new(File::open(archive)?);
+    let reader: Box<dyn Read> = match kind {
+        Kind::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn not_found(member: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("no member '{}'", member))
+}
+
+/// Finds `member` in the archive and hands its contents to `read`.
+fn with_member(
+    archive: &str,
+    kind: Kind,
+    member: &str,
+    read: impl FnOnce(&mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let wanted = normalize(member);
+    match kind {
+        Kind::Tar | Kind::TarGz => {
+            let mut tar = tar_reader(archive, kind)?;
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                if normalize(&String::from_utf8_lossy(&entry.path_bytes())) == wanted {
+                    return read(&mut entry);
+                }
+            }
+            Err(not_found(member))
+        }
+        Kind::Zip => {
+            let mut zip = ZipArchive::new(File::open(archive)?)?;
+            let index = (0..zip.len())
+                .find(|&i| zip.name_for_index(i).is_some_and(|name| normalize(name) == wanted))
+                .ok_or_else(|| not_found(member))?;
+            let mut file = zip.by_index(index)?;
+            read(&mut file)
+        }
+    }
+}
+
+/// Opens a member for reading. A missing archive or member is reported here
+/// rather than by the first read.
+pub(crate) fn open(archive: &str, member: &str) -> Result<Box<dyn BufRead>> {
+    let kind = kind(archive)?;
+    let (tx, rx) = mpsc::sync_channel(CHUNKS_AHEAD);
+    let (archive, member) = (archive.to_string(), member.to_string());
+    thread::spawn(move || {
+        let sent = with_member(&archive, kind, &member, |reader| {
+            // An empty chunk first says the member was found.
+            tx.send(Ok(Vec::new())).map_err(|_| io::ErrorKind::BrokenPipe)?;
+            send_chunks(reader, &tx)
+        });
+        if let Err(err) = sent {
+            // Nobody is listening any more if the output went away.
+            let _ = tx.send(Err(err));
+        }
+    });
+
+    match rx.recv() {
+        Ok(Err(err)) => Err(err.into()),
+        _ => Ok(Box::new(BufReader::new(Chunks { rx, chunk: Vec::new(), pos: 0 }))),
+    }
+}
+
+fn send_chunks(reader: &mut dyn Read, tx: &SyncSender<io::Result<Vec<u8>>>) -> io::Result<()> {
+    loop {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        if reader.take(CHUNK_SIZE as u64).read_to_end(&mut chunk)? == 0 {
+            return Ok(());
+        }
+        tx.send(Ok(chunk)).map_err(|_| io::ErrorKind::BrokenPipe)?;
+    }
+}
+
+/// The reading end of the channel from the thread reading a member.
+struct Chunks {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => (self.chunk, self.pos) = (chunk?, 0),
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Prints the member names of an archive, one per line, each after `prefix`.
+pub(crate) fn list(archive: &str, prefix: &str, out: &mut impl Write) -> Result<()> {
+    match kind(archive)? {
+        kind @ (Kind::Tar | Kind::TarGz) => {
+            let mut tar = tar_reader(archive, kind)?;
+            for entry in tar.entries()? {
+                let entry = entry?;
+                let path = entry.path_bytes();
+                writeln!(out, "{}{}", prefix, normalize(&String::from_utf8_lossy(&path)))?;
+            }
+        }
+        Kind::Zip => {
+            let zip = ZipArchive::new(File::open(archive)?)?;
+            for name in (0..zip.len()).filter_map(|i| zip.name_for_index(i)) {
+                writeln!(out, "{}{}", prefix, normalize(name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_spec() {
+        assert_eq!(split_spec("bundle.tar.gz:etc/app.conf"), Some(("bundle.tar.gz", "etc/app.conf")));
+        assert_eq!(split_spec("release.ZIP:README.md"), Some(("release.ZIP", "README.md")));
+        assert_eq!(split_spec("a.tgz:b.zip:c"), Some(("a.tgz", "b.zip:c")));
+        assert_eq!(split_spec("notes.txt:12"), None);
+        assert_eq!(split_spec("Cargo.toml"), None);
+    }
+}
@@ -0,0 +1,457 @@
+//! The line formatting behind catr, as a library: numbering, terminator
+//! rewriting, sanitizing, highlighting and truncation.
+//!
+//! ```
+//! use catr::Cat;
+//!
+//! let cat = Cat::new().number_nonblank_lines(true);
+//! let mut out = Vec::new();
+//! cat.write(["one\n\ntwo\n".as_bytes()], &mut out).unwrap();
+//! assert_eq!(out, b"     1\tone\n\n     2\ttwo\n");
+//! ```
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+
+use crate::eol::{self, Eol};
+use crate::highlight::{Assets, Highlighter};
+use crate::redact::Redactor;
+use crate::timestamp::{TimestampMode, Timestamper};
+use crate::{control, invisible, truncate};
+
+/// Called with the source name, line number and characters found by
+/// [`Cat::on_invisible`].
+type InvisibleReport = dyn Fn(&str, usize, &[char]);
+
+/// Options for formatting lines, set with builder methods. Each source
+/// passed to [`Cat::write`] or [`Cat::records`] is numbered from 1, like
+/// each file on the command line.
+pub struct Cat {
+    number_lines: bool,
+    number_nonblank_lines: bool,
+    eol: Eol,
+    zero_terminated: bool,
+    with_filename: bool,
+    timestamper: Option<Timestamper>,
+    redactor: Option<Redactor>,
+    strip_ansi: bool,
+    escape_control: bool,
+    reveal_invisible: bool,
+    deny_invisible: bool,
+    on_invisible: Option<Box<InvisibleReport>>,
+    invisible_found: Cell<usize>,
+    truncate: Option<usize>,
+    ellipsis: String,
+    assets: Option<Assets>,
+}
+
+impl Default for Cat {
+    fn default() -> Self {
+        Cat {
+            number_lines: false,
+            number_nonblank_lines: false,
+            eol: Eol::Keep,
+            zero_terminated: false,
+            with_filename: false,
+            timestamper: None,
+            redactor: None,
+            strip_ansi: false,
+            escape_control: false,
+            reveal_invisible: false,
+            deny_invisible: false,
+            on_invisible: None,
+            invisible_found: Cell::new(0),
+            truncate: None,
+            ellipsis: "…".to_string(),
+            assets: None,
+        }
+    }
+}
+
+impl Cat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number all lines (`-n`).
+    pub fn number_lines(mut self, yes: bool) -> Self {
+        self.number_lines = yes;
+        self
+    }
+
+    /// Number nonblank lines (`-b`); takes precedence over `number_lines`.
+    pub fn number_nonblank_lines(mut self, yes: bool) -> Self {
+        self.number_nonblank_lines = yes;
+        self
+    }
+
+    /// Rewrite line terminators (`--eol`).
+    pub fn eol(mut self, eol: Eol) -> Self {
+        self.eol = eol;
+        self
+    }
+
+    /// End records with NUL instead of newline (`-z`), as written by
+    /// `find -print0`.
+    pub fn zero_terminated(mut self, yes: bool) -> Self {
+        self.zero_terminated = yes;
+        self
+    }
+
+    /// Start each line with the name of its file and a colon
+    /// (`--with-filename`); sources passed to [`Cat::write`] are named `-`.
+    pub fn with_filename(mut self, yes: bool) -> Self {
+        self.with_filename = yes;
+        self
+    }
+
+    /// Start each line with the moment it was read, in strftime `format`
+    /// or that of `ts` (`--timestamp`). Fails for an invalid format.
+    pub fn timestamp(mut self, format: Option<&str>, mode: TimestampMode) -> Result<Self> {
+        self.timestamper = Some(Timestamper::new(format, mode)?);
+        Ok(self)
+    }
+
+    /// Replace matches of `patterns`, each a preset name, `all`, or a
+    /// regex, with `[REDACTED:kind]` (`--redact`). Fails for an invalid
+    /// regex.
+    pub fn redact(mut self, patterns: &[String]) -> Result<Self> {
+        self.redactor = Some(Redactor::new(patterns)?);
+        Ok(self)
+    }
+
+    /// Remove terminal escape sequences (`--strip-ansi`).
+    pub fn strip_ansi(mut self, yes: bool) -> Self {
+        self.strip_ansi = yes;
+        self
+    }
+
+    /// Show control characters visibly (`--escape-control`).
+    pub fn escape_control(mut self, yes: bool) -> Self {
+        self.escape_control = yes;
+        self
+    }
+
+    /// Mark invisible and confusable characters (`--reveal-invisible`).
+    pub fn reveal_invisible(mut self, yes: bool) -> Self {
+        self.reveal_invisible = yes;
+        self
+    }
+
+    /// Look for the characters of `reveal_invisible` without marking them
+    /// (`--deny-invisible`); see [`Cat::invisible_found`].
+    pub fn deny_invisible(mut self, yes: bool) -> Self {
+        self.deny_invisible = yes;
+        self
+    }
+
+    /// Call `report` with the source name, line number and characters of
+    /// each line where invisible or confusable characters are found.
+    pub fn on_invisible(mut self, report: impl Fn(&str, usize, &[char]) + 'static) -> Self {
+        self.on_invisible = Some(Box::new(report));
+        self
+    }
+
+    /// Cut lines to `width` terminal cells (`--truncate`).
+    pub fn truncate(mut self, width: Option<usize>) -> Self {
+        self.truncate = width;
+        self
+    }
+
+    /// What cut lines end with (`--ellipsis`).
+    pub fn ellipsis(mut self, ellipsis: &str) -> Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
+    /// Colorize lines with a syntect theme (`--highlight`). Fails for an
+    /// unknown theme or language.
+    pub fn highlight(mut self, theme: &str, language: Option<&str>) -> Result<Self> {
+        self.assets = Some(Assets::load(theme, language)?);
+        Ok(self)
+    }
+
+    /// Formats every line of every source into `out`.
+    pub fn write<I, W>(&self, sources: I, out: &mut W) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: BufRead,
+        W: Write,
+    {
+        let mut printer: Option<Printer> = None;
+        for mut source in sources {
+            if let Some(last) = &printer {
+                last.end_line(out)?;
+            }
+            let printer = printer.insert(self.printer("-"));
+            print_lines(&mut source, printer, out)?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over the formatted lines of every source, each with its
+    /// terminator (if it had one).
+    pub fn records<I>(&self, sources: I) -> Records<'_, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: BufRead,
+    {
+        Records {
+            cat: self,
+            sources: sources.into_iter(),
+            current: None,
+            line: Vec::new(),
+        }
+    }
+
+    /// How many invisible or confusable characters were found so far.
+    pub fn invisible_found(&self) -> usize {
+        self.invisible_found.get()
+    }
+
+    /// How many matches of each kind [`Cat::redact`] has replaced so far,
+    /// in the order the kinds were first given.
+    pub fn redacted(&self) -> Vec<(&'static str, usize)> {
+        self.redactor.as_ref().map_or_else(Vec::new, Redactor::counts)
+    }
+
+    /// A printer for one file; `filename` is used in messages and for
+    /// detecting the language to highlight.
+    pub(crate) fn printer(&self, filename: &str) -> Printer<'_> {
+        Printer {
+            cat: self,
+            filename: filename.to_string(),
+            highlighter: self.assets.as_ref().map(|assets| Highlighter::new(assets, filename)),
+            line_num: 0,
+            last_num: 0,
+            unterminated: false,
+        }
+    }
+}
+
+/// Iterator returned by [`Cat::records`].
+pub struct Records<'a, I: Iterator> {
+    cat: &'a Cat,
+    sources: I,
+    current: Option<(I::Item, Printer<'a>)>,
+    line: Vec<u8>,
+}
+
+impl<I> Iterator for Records<'_, I>
+where
+    I: Iterator,
+    I::Item: BufRead,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let source = self.sources.next()?;
+                self.current = Some((source, self.cat.printer("-")));
+            }
+            let (source, printer) = self.current.as_mut()?;
+
+            self.line.clear();
+            match printer.read_record(source, &mut self.line) {
+                Err(err) => return Some(Err(err)),
+                Ok(0) => self.current = None,
+                Ok(_) => {
+                    let mut record = Vec::with_capacity(self.line.len() + 8);
+                    return Some(printer.print(&mut record, &self.line).map(|_| record));
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn print_lines(
+    file: &mut impl BufRead,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    while printer.read_record(file, &mut line)? > 0 {
+        printer.print(out, &line)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Writes lines to the output, keeping the numbering state of one file.
+pub(crate) struct Printer<'a> {
+    cat: &'a Cat,
+    filename: String,
+    highlighter: Option<Highlighter<'a>>,
+    /// Lines read so far, blank or not.
+    line_num: usize,
+    last_num: usize,
+    /// Whether the last line printed had no terminator.
+    unterminated: bool,
+}
+
+impl Printer<'_> {
+    /// Whether a bare CR ends a line, which is the case once terminators
+    /// are rewritten.
+    pub(crate) fn any_eol(&self) -> bool {
+        self.cat.eol != Eol::Keep
+    }
+
+    /// The byte that ends a record: NUL with `-z`, else newline.
+    pub(crate) fn delimiter(&self) -> u8 {
+        if self.cat.zero_terminated { b'\0' } else { b'\n' }
+    }
+
+    /// Reads one record, delimiter included, into `buf`.
+    pub(crate) fn read_record(
+        &self,
+        reader: &mut impl BufRead,
+        buf: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        if self.cat.zero_terminated {
+            reader.read_until(b'\0', buf)
+        } else {
+            eol::read_line(reader, buf, self.any_eol())
+        }
+    }
+
+    /// Ends a numbered last line that had no terminator, so that the next
+    /// file's first number does not land on it.
+    pub(crate) fn end_line(&self, out: &mut impl Write) -> io::Result<()> {
+        let numbered = self.cat.number_lines || self.cat.number_nonblank_lines;
+        if !numbered || !self.unterminated {
+            return Ok(());
+        }
+        match self.cat.eol.terminator() {
+            Some(eol) => out.write_all(eol),
+            None => out.write_all(&[self.delimiter()]),
+        }
+    }
+
+    /// Prints one line (or NUL-terminated record), which still carries its
+    /// terminator (if any).
+    pub(crate) fn print(&mut self, out: &mut impl Write, line: &[u8]) -> io::Result<()> {
+        let cat = self.cat;
+        self.line_num += 1;
+        let (text, terminator) = match line.split_last() {
+            Some((&b'\0', text)) if self.cat.zero_terminated => (text, &line[text.len()..]),
+            _ => eol::split_terminator(line, self.any_eol()),
+        };
+        let mut text = Cow::Borrowed(text);
+
+        if cat.strip_ansi {
+            text = Cow::Owned(control::strip_ansi(&text));
+        }
+        if let Some(redactor) = &cat.redactor {
+            if let Cow::Owned(redacted) = redactor.redact(&text) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        if cat.escape_control {
+            text = Cow::Owned(control::escape_control(&text));
+        }
+
+        if cat.reveal_invisible || cat.deny_invisible {
+            let (revealed, found) = invisible::reveal(&text);
+            if !found.is_empty() {
+                if let Some(report) = &cat.on_invisible {
+                    report(&self.filename, self.line_num, &found);
+                }
+            }
+            cat.invisible_found.set(cat.invisible_found.get() + found.len());
+            if cat.reveal_invisible {
+                text = Cow::Owned(revealed);
+            }
+        }
+
+        if let Some(timestamper) = &cat.timestamper {
+            write!(out, "{} ", timestamper.stamp())?;
+        }
+        if cat.with_filename {
+            write!(out, "{}:", self.filename)?;
+        }
+
+        // A CRLF line is blank too when terminators are kept as they are.
+        let blank = text.is_empty() || (&text[..] == b"\r" && terminator == b"\n");
+        if cat.number_nonblank_lines {
+            if !blank {
+                self.last_num += 1;
+                write!(out, "{:6}\t", self.last_num)?;
+            }
+        } else if cat.number_lines {
+            self.last_num += 1;
+            write!(out, "{:6}\t", self.last_num)?;
+        }
+        if let Some(colored) = self.highlighter.as_mut().and_then(|h| h.highlight(&text)) {
+            text = Cow::Owned(colored.into_bytes());
+        }
+        if let Some(width) = cat.truncate {
+            text = Cow::Owned(truncate::truncate(&text, width, &cat.ellipsis).into_owned());
+        }
+        out.write_all(&text)?;
+        self.unterminated = terminator.is_empty();
+        match cat.eol.terminator() {
+            Some(eol) if !terminator.is_empty() => out.write_all(eol),
+            _ => out.write_all(terminator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_records() {
+        let cat = Cat::new().number_lines(true).eol(Eol::Lf);
+        let sources = [Cursor::new("a\r\nb"), Cursor::new("c\n")];
+        let records: Vec<_> = cat.records(sources).collect::<io::Result<_>>().unwrap();
+
+        assert_eq!(
+            records,
+            [&b"     1\ta\n"[..], b"     2\tb", b"     1\tc\n"],
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let cat = Cat::new().timestamp(Some("%H:%M"), TimestampMode::SinceStart).unwrap();
+        let mut out = Vec::new();
+        cat.write([Cursor::new("a\n")], &mut out).unwrap();
+
+        assert_eq!(out, b"00:00 a\n");
+    }
+
+    #[test]
+    fn test_redact() {
+        let cat = Cat::new().redact(&["email".to_string(), "ipv4".to_string()]).unwrap();
+        let mut out = Vec::new();
+        cat.write([Cursor::new("a@b.io at 10.0.0.1\n")], &mut out).unwrap();
+
+        assert_eq!(out, b"[REDACTED:email] at [REDACTED:ipv4]\n");
+        assert_eq!(cat.redacted(), [("email", 1), ("ipv4", 1)]);
+    }
+
+    #[test]
+    fn test_deny_invisible() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reported = Rc::clone(&reports);
+        let cat = Cat::new()
+            .deny_invisible(true)
+            .on_invisible(move |name, line_num, found| {
+                reported.borrow_mut().push((name.to_string(), line_num, found.to_vec()));
+            });
+        let mut out = Vec::new();
+        cat.write([Cursor::new("a\nb\u{200B}\n")], &mut out).unwrap();
+
+        assert_eq!(out, "a\nb\u{200B}\n".as_bytes());
+        assert_eq!(cat.invisible_found(), 1);
+        assert_eq!(*reports.borrow(), [("-".to_string(), 2, vec!['\u{200B}'])]);
+    }
+}
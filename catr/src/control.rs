@@ -0,0 +1,116 @@
+//! `--strip-ansi` and `--escape-control`: keep untrusted text from driving
+//! the terminal it is shown on.
+
+const ESC: u8 = 0x1B;
+const BEL: u8 = 0x07;
+
+/// Where an escape sequence starts, and how it ends.
+enum Intro {
+    /// Control Sequence Introducer: parameters and one final byte.
+    Csi(usize),
+    /// OSC, DCS, SOS, PM and APC: a string up to BEL or String Terminator.
+    Str(usize),
+    /// Any other escape: intermediates and one final byte.
+    Esc,
+}
+
+/// Recognizes both the 7-bit (ESC-prefixed) and the UTF-8 encoded 8-bit
+/// forms of the introducers.
+fn intro(text: &[u8]) -> Option<Intro> {
+    match text {
+        [ESC, b'[', ..] => Some(Intro::Csi(2)),
+        [ESC, b']' | b'P' | b'X' | b'^' | b'_', ..] => Some(Intro::Str(2)),
+        [ESC, ..] => Some(Intro::Esc),
+        [0xC2, 0x9B, ..] => Some(Intro::Csi(2)),
+        [0xC2, 0x90 | 0x98 | 0x9D | 0x9E | 0x9F, ..] => Some(Intro::Str(2)),
+        _ => None,
+    }
+}
+
+/// Removes escape sequences. A sequence cut off by the end of the line is
+/// removed up to there.
+pub(crate) fn strip_ansi(text: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        let Some(intro) = intro(&text[i..]) else {
+            stripped.push(text[i]);
+            i += 1;
+            continue;
+        };
+
+        i += match intro {
+            Intro::Csi(len) => {
+                let rest = &text[i + len..];
+                let params = rest.iter().take_while(|b| (0x20..=0x3F).contains(*b)).count();
+                let fin = usize::from(rest.get(params).is_some_and(|b| (0x40..=0x7E).contains(b)));
+                len + params + fin
+            }
+            Intro::Str(len) => {
+                let rest = &text[i + len..];
+                let end = (0..rest.len()).find_map(|j| match &rest[j..] {
+                    [BEL, ..] => Some(j + 1),
+                    [ESC, b'\\', ..] | [0xC2, 0x9C, ..] => Some(j + 2),
+                    _ => None,
+                });
+                len + end.unwrap_or(rest.len())
+            }
+            Intro::Esc => {
+                let rest = &text[i + 1..];
+                let inter = rest.iter().take_while(|b| (0x20..=0x2F).contains(*b)).count();
+                let fin = usize::from(rest.get(inter).is_some_and(|b| (0x30..=0x7E).contains(b)));
+                1 + inter + fin
+            }
+        };
+    }
+
+    stripped
+}
+
+/// Makes control characters visible: C0 controls and DEL in caret notation
+/// (`^[`, `^?`), C1 controls as `<U+009B>`, and stray bytes that would be C1
+/// controls in an 8-bit terminal as `\x9b`. Tabs are left alone.
+pub(crate) fn escape_control(text: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(text.len());
+    for chunk in text.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\t' => escaped.push(b'\t'),
+                '\0'..='\x1F' => escaped.extend_from_slice(&[b'^', c as u8 + 0x40]),
+                '\x7F' => escaped.extend_from_slice(b"^?"),
+                '\u{80}'..='\u{9F}' => {
+                    escaped.extend_from_slice(format!("<U+{:04X}>", c as u32).as_bytes())
+                }
+                _ => escaped.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        for b in chunk.invalid() {
+            match b {
+                0x80..=0x9F => escaped.extend_from_slice(format!("\\x{:02x}", b).as_bytes()),
+                _ => escaped.push(*b),
+            }
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        let text = b"\x1b[1;31mred\x1b[0m \x1b]0;title\x07plain \x1b]8;;http://x\x1b\\link\x1b(B";
+        assert_eq!(strip_ansi(text), b"red plain link");
+        assert_eq!(strip_ansi("a\u{9b}2Jb\x1b".as_bytes()), b"ab");
+        assert_eq!(strip_ansi(b"cut \x1b]0;never ends"), b"cut ");
+    }
+
+    #[test]
+    fn test_escape_control() {
+        let text = "tab\there\x1b[2J\r\x7f\u{9b}\u{e9}".as_bytes();
+        assert_eq!(escape_control(text), "tab\there^[[2J^M^?<U+009B>\u{e9}".as_bytes());
+        assert_eq!(escape_control(b"\x9b\xff"), b"\\x9b\xff");
+    }
+}
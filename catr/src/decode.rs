@@ -0,0 +1,271 @@
+//! `--from-encoding`: a reader that turns UTF-16, UTF-32 and the legacy
+//! single- and double-byte encodings into UTF-8 before catr sees the text.
+
+use std::io::{self, Read};
+
+use clap::ValueEnum;
+use encoding_rs::{CoderResult, Decoder, DecoderResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// Detect a BOM; input without one is read as UTF-8
+    Auto,
+    #[value(name = "utf-8", alias("utf8"))]
+    Utf8,
+    #[value(name = "utf-16le", alias("utf16le"))]
+    Utf16le,
+    #[value(name = "utf-16be", alias("utf16be"))]
+    Utf16be,
+    #[value(name = "utf-32le", alias("utf32le"))]
+    Utf32le,
+    #[value(name = "utf-32be", alias("utf32be"))]
+    Utf32be,
+    #[value(name = "latin1", alias("iso-8859-1"))]
+    Latin1,
+    #[value(name = "windows-1252", alias("cp1252"))]
+    Windows1252,
+    #[value(name = "shift_jis", alias("sjis"))]
+    ShiftJis,
+    #[value(name = "gbk")]
+    Gbk,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Auto => "auto",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16le => "UTF-16LE",
+            Encoding::Utf16be => "UTF-16BE",
+            Encoding::Utf32le => "UTF-32LE",
+            Encoding::Utf32be => "UTF-32BE",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::Gbk => "GBK",
+        }
+    }
+
+    /// Picks the encoding from a byte order mark, returning it together
+    /// with the length of the mark.
+    fn sniff(bytes: &[u8]) -> Option<(Encoding, usize)> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+            [0xFF, 0xFE, 0x00, 0x00, ..] => Some((Encoding::Utf32le, 4)),
+            [0x00, 0x00, 0xFE, 0xFF, ..] => Some((Encoding::Utf32be, 4)),
+            [0xFF, 0xFE, ..] => Some((Encoding::Utf16le, 2)),
+            [0xFE, 0xFF, ..] => Some((Encoding::Utf16be, 2)),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with bytes that are not valid in the input encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnInvalid {
+    /// Write U+FFFD REPLACEMENT CHARACTER instead
+    Replace,
+    /// Stop with an error
+    Error,
+}
+
+enum Converter {
+    /// Still waiting for enough bytes to look for a BOM.
+    Sniffing,
+    EncodingRs(Decoder),
+    Utf32 { big_endian: bool },
+    Latin1,
+}
+
+pub(crate) struct DecodeReader<R> {
+    inner: R,
+    encoding: Encoding,
+    on_invalid: OnInvalid,
+    converter: Converter,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    /// Bytes taken out of `input` so far, for error messages.
+    consumed: u64,
+    eof: bool,
+    /// The converter has seen the end of the input and must not be used
+    /// again.
+    finished: bool,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub(crate) fn new(inner: R, encoding: Encoding, on_invalid: OnInvalid) -> Self {
+        DecodeReader {
+            inner,
+            encoding,
+            on_invalid,
+            converter: Self::converter(encoding),
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            consumed: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    /// A leading BOM is dropped for every encoding that has one.
+    fn converter(encoding: Encoding) -> Converter {
+        let decoder = |enc: &'static encoding_rs::Encoding| {
+            Converter::EncodingRs(enc.new_decoder_with_bom_removal())
+        };
+        match encoding {
+            Encoding::Auto => Converter::Sniffing,
+            Encoding::Utf8 => decoder(encoding_rs::UTF_8),
+            Encoding::Utf16le => decoder(encoding_rs::UTF_16LE),
+            Encoding::Utf16be => decoder(encoding_rs::UTF_16BE),
+            Encoding::Utf32le => Converter::Utf32 { big_endian: false },
+            Encoding::Utf32be => Converter::Utf32 { big_endian: true },
+            Encoding::Latin1 => Converter::Latin1,
+            Encoding::Windows1252 => decoder(encoding_rs::WINDOWS_1252),
+            Encoding::ShiftJis => decoder(encoding_rs::SHIFT_JIS),
+            Encoding::Gbk => decoder(encoding_rs::GBK),
+        }
+    }
+
+    /// Reads and converts until there is some output or the input ends.
+    fn fill(&mut self) -> io::Result<()> {
+        self.output.clear();
+        self.output_pos = 0;
+
+        while self.output.is_empty() && !self.finished {
+            let mut chunk = [0; 8192];
+            let n = if self.eof { 0 } else { self.inner.read(&mut chunk)? };
+            self.eof = n == 0;
+            self.input.extend_from_slice(&chunk[..n]);
+
+            if let Converter::Sniffing = self.converter {
+                if self.input.len() < 4 && !self.eof {
+                    continue;
+                }
+                // Without a BOM the input is taken to be UTF-8, so that
+                // `--on-invalid` still applies to it.
+                let (encoding, bom_len) =
+                    Encoding::sniff(&self.input).unwrap_or((Encoding::Utf8, 0));
+                self.encoding = encoding;
+                self.input.drain(..bom_len);
+                self.consumed += bom_len as u64;
+                self.converter = Self::converter(encoding);
+            }
+
+            self.convert()?;
+            self.finished = self.eof;
+        }
+        Ok(())
+    }
+
+    /// Moves as much of `input` as can be converted into `output`.
+    fn convert(&mut self) -> io::Result<()> {
+        let last = self.eof;
+        let read = match &mut self.converter {
+            Converter::Sniffing => 0,
+            Converter::Latin1 => {
+                let text: String = self.input.iter().map(|&b| b as char).collect();
+                self.output.extend_from_slice(text.as_bytes());
+                self.input.len()
+            }
+            Converter::Utf32 { big_endian } => {
+                let big_endian = *big_endian;
+                let mut read = 0;
+                for unit in self.input.chunks(4) {
+                    let c = match <[u8; 4]>::try_from(unit) {
+                        Ok(unit) if big_endian => char::from_u32(u32::from_be_bytes(unit)),
+                        Ok(unit) => char::from_u32(u32::from_le_bytes(unit)),
+                        // A few stray bytes can only be completed by more input.
+                        Err(_) if !last => break,
+                        Err(_) => None,
+                    };
+                    let c = match c {
+                        // The BOM is only dropped at the very start.
+                        Some('\u{FEFF}') if self.consumed == 0 && read == 0 => {
+                            read += unit.len();
+                            continue;
+                        }
+                        Some(c) => c,
+                        None if self.on_invalid == OnInvalid::Replace => '\u{FFFD}',
+                        None => {
+                            return Err(invalid(self.encoding, self.consumed + read as u64));
+                        }
+                    };
+                    self.output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    read += unit.len();
+                }
+                read
+            }
+            Converter::EncodingRs(decoder) => {
+                let mut read = 0;
+                loop {
+                    let src = &self.input[read..];
+                    let start = self.output.len();
+                    match self.on_invalid {
+                        OnInvalid::Replace => {
+                            let room = decoder
+                                .max_utf8_buffer_length(src.len())
+                                .unwrap_or(src.len() * 3 + 16);
+                            self.output.resize(start + room, 0);
+                            let (result, n, written, _) =
+                                decoder.decode_to_utf8(src, &mut self.output[start..], last);
+                            self.output.truncate(start + written);
+                            read += n;
+                            if result == CoderResult::InputEmpty {
+                                break;
+                            }
+                        }
+                        OnInvalid::Error => {
+                            let room = decoder
+                                .max_utf8_buffer_length_without_replacement(src.len())
+                                .unwrap_or(src.len() * 3 + 16);
+                            self.output.resize(start + room, 0);
+                            let (result, n, written) = decoder
+                                .decode_to_utf8_without_replacement(
+                                    src,
+                                    &mut self.output[start..],
+                                    last,
+                                );
+                            self.output.truncate(start + written);
+                            read += n;
+                            match result {
+                                DecoderResult::InputEmpty => break,
+                                DecoderResult::OutputFull => {}
+                                DecoderResult::Malformed(bad, extra) => {
+                                    let bad = bad as usize + extra as usize;
+                                    let at = self.consumed + (read - bad) as u64;
+                                    return Err(invalid(self.encoding, at));
+                                }
+                            }
+                        }
+                    }
+                }
+                read
+            }
+        };
+        self.input.drain(..read);
+        self.consumed += read as u64;
+        Ok(())
+    }
+}
+
+fn invalid(encoding: Encoding, at: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid {} data at byte {}", encoding.name(), at),
+    )
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output_pos == self.output.len() {
+            self.fill()?;
+        }
+        let available = &self.output[self.output_pos..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.output_pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,138 @@
+//! `--eol` and `--report-eol`: rewriting and counting line terminators.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Eol {
+    /// End lines with "\n"
+    Lf,
+    /// End lines with "\r\n"
+    Crlf,
+    /// End lines with "\r"
+    Cr,
+    /// Leave terminators as they are
+    Keep,
+}
+
+impl Eol {
+    /// The terminator to write, or `None` to write the one that was read.
+    pub(crate) fn terminator(self) -> Option<&'static [u8]> {
+        match self {
+            Eol::Lf => Some(b"\n"),
+            Eol::Crlf => Some(b"\r\n"),
+            Eol::Cr => Some(b"\r"),
+            Eol::Keep => None,
+        }
+    }
+}
+
+/// Reads one line, terminator included, into `buf`. With `any_eol` a bare
+/// "\r" ends a line too, and "\r\n" is kept together even when it straddles
+/// two reads.
+pub(crate) fn read_line(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    any_eol: bool,
+) -> io::Result<usize> {
+    if !any_eol {
+        return reader.read_until(b'\n', buf);
+    }
+
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(read);
+        }
+        match available.iter().position(|&b| b == b'\n' || b == b'\r') {
+            Some(i) => {
+                let cr = available[i] == b'\r';
+                buf.extend_from_slice(&available[..=i]);
+                reader.consume(i + 1);
+                read += i + 1;
+                if cr && reader.fill_buf()?.first() == Some(&b'\n') {
+                    buf.push(b'\n');
+                    reader.consume(1);
+                    read += 1;
+                }
+                return Ok(read);
+            }
+            None => {
+                let n = available.len();
+                buf.extend_from_slice(available);
+                reader.consume(n);
+                read += n;
+            }
+        }
+    }
+}
+
+/// Splits a line read by `read_line` into its text and its terminator.
+pub(crate) fn split_terminator(line: &[u8], any_eol: bool) -> (&[u8], &[u8]) {
+    let len = if line.ends_with(b"\r\n") && any_eol {
+        2
+    } else if line.ends_with(b"\n") || (line.ends_with(b"\r") && any_eol) {
+        1
+    } else {
+        0
+    };
+    line.split_at(line.len() - len)
+}
+
+/// Line terminator counts of one file, for `--report-eol`.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct EolReport {
+    lf: u64,
+    crlf: u64,
+    cr: u64,
+    final_newline: bool,
+}
+
+impl EolReport {
+    pub(crate) fn count(reader: &mut impl BufRead) -> io::Result<Self> {
+        let mut report = EolReport { final_newline: true, ..Default::default() };
+        let mut line = Vec::new();
+        while read_line(reader, &mut line, true)? > 0 {
+            match split_terminator(&line, true).1 {
+                b"\r\n" => report.crlf += 1,
+                b"\n" => report.lf += 1,
+                b"\r" => report.cr += 1,
+                _ => report.final_newline = false,
+            }
+            line.clear();
+        }
+        Ok(report)
+    }
+}
+
+impl fmt::Display for EolReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "lf={} crlf={} cr={} final-newline={}",
+            self.lf,
+            self.crlf,
+            self.cr,
+            if self.final_newline { "yes" } else { "no" },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn test_count() {
+        // A one-byte buffer makes every "\r\n" straddle two reads.
+        let text = "unix\ndos\r\nmac\rlast";
+        let report = EolReport::count(&mut BufReader::with_capacity(1, Cursor::new(text)));
+
+        let expected = EolReport { lf: 1, crlf: 1, cr: 1, final_newline: false };
+        assert_eq!(report.unwrap(), expected);
+    }
+}
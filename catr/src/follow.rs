@@ -0,0 +1,220 @@
+//! `-f/--follow`: keep printing the last file as it grows, the way `tail -F`
+//! does. Truncation rewinds to the start, and a rotated file (the path now
+//! names a different inode) is drained and then reopened.
+
+use std::fs::{self, File, Metadata};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::cat::Printer;
+
+/// How long to sleep between checks when inotify is not available.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Upper bound on a single inotify wait, so `--pid` is checked regularly.
+#[cfg(target_os = "linux")]
+const WAIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub(crate) fn follow(
+    filename: &str,
+    printer: &mut Printer,
+    out: &mut impl Write,
+    pid: Option<u32>,
+) -> Result<()> {
+    let file = File::open(filename)?;
+    let mut id = file_id(&file.metadata()?);
+    let mut reader = BufReader::new(file);
+    let mut pos = 0;
+    let mut line = Vec::new();
+    let mut waiter = Waiter::new(Path::new(filename));
+    let mut done = false;
+
+    loop {
+        pos += drain(&mut reader, &mut line, printer, out)?;
+        out.flush()?;
+        if done {
+            break;
+        }
+
+        waiter.wait();
+        // Read once more after the process has gone, so its last writes
+        // are not lost.
+        done = pid.is_some_and(|pid| !is_alive(pid));
+
+        if reader.get_ref().metadata()?.len() < pos {
+            eprintln!("{}: file truncated", filename);
+            finish_line(&mut line, printer, out)?;
+            reader.seek(SeekFrom::Start(0))?;
+            pos = 0;
+            continue;
+        }
+
+        if let Ok(meta) = fs::metadata(filename) {
+            if file_id(&meta) != id {
+                drain(&mut reader, &mut line, printer, out)?;
+                finish_line(&mut line, printer, out)?;
+                if let Ok(file) = File::open(filename) {
+                    eprintln!("{}: file replaced; following new file", filename);
+                    id = file_id(&file.metadata()?);
+                    reader = BufReader::new(file);
+                    pos = 0;
+                }
+            }
+        }
+    }
+
+    finish_line(&mut line, printer, out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Prints a partial line that will never get its newline, e.g. because the
+/// file it came from was truncated or replaced.
+fn finish_line(
+    line: &mut Vec<u8>,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> Result<()> {
+    if !line.is_empty() {
+        printer.print(out, line)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Prints every complete line currently available and returns the number of
+/// bytes consumed. A trailing partial line stays in `line` until the rest of
+/// it is written.
+fn drain(
+    reader: &mut impl BufRead,
+    line: &mut Vec<u8>,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> Result<u64> {
+    let any_eol = printer.any_eol();
+    let mut consumed = 0;
+    loop {
+        // A line that ended in "\r" right at the end of the data may still
+        // turn out to be "\r\n".
+        if any_eol && line.ends_with(b"\r") {
+            match reader.fill_buf()?.first() {
+                None => return Ok(consumed),
+                Some(b'\n') => {
+                    line.push(b'\n');
+                    reader.consume(1);
+                    consumed += 1;
+                }
+                Some(_) => {}
+            }
+            printer.print(out, line)?;
+            line.clear();
+        }
+
+        let n = printer.read_record(reader, line)?;
+        if n == 0 {
+            return Ok(consumed);
+        }
+        consumed += n as u64;
+        if line.last() == Some(&printer.delimiter()) {
+            printer.print(out, line)?;
+            line.clear();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks for existence; EPERM means it exists but is
+    // owned by someone else.
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Blocks until the followed file may have changed.
+enum Waiter {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Poll,
+}
+
+impl Waiter {
+    /// Watches the parent directory rather than the file, so the watch
+    /// survives the file being renamed or replaced.
+    #[cfg(target_os = "linux")]
+    fn new(path: &Path) -> Self {
+        use inotify::{Inotify, WatchMask};
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mask = WatchMask::MODIFY
+            | WatchMask::ATTRIB
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO;
+
+        match Inotify::init() {
+            Ok(inotify) if inotify.watches().add(dir, mask).is_ok() => {
+                Waiter::Inotify(inotify)
+            }
+            _ => Waiter::Poll,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new(_path: &Path) -> Self {
+        Waiter::Poll
+    }
+
+    fn wait(&mut self) {
+        match self {
+            #[cfg(target_os = "linux")]
+            Waiter::Inotify(inotify) => {
+                use std::os::fd::AsRawFd;
+
+                let mut fds = libc::pollfd {
+                    fd: inotify.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let timeout = WAIT_TIMEOUT.as_millis() as libc::c_int;
+                unsafe { libc::poll(&mut fds, 1, timeout) };
+
+                // The events themselves do not matter, only that something
+                // happened; empty the queue for the next wait.
+                let mut buffer = [0; 4096];
+                while let Ok(mut events) = inotify.read_events(&mut buffer) {
+                    if events.next().is_none() {
+                        break;
+                    }
+                }
+            }
+            Waiter::Poll => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
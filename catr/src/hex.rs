@@ -0,0 +1,192 @@
+//! `--hex` and `--hex-reverse`: an xxd-style dump of the input and the way
+//! back. The dump treats all inputs as one stream, so offsets continue from
+//! one file to the next, just like `cat a b | xxd`.
+
+use std::io::{self, BufRead, Read, Write};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OffsetBase {
+    Hex,
+    Dec,
+    Oct,
+}
+
+impl OffsetBase {
+    fn format(self, offset: u64) -> String {
+        match self {
+            OffsetBase::Hex => format!("{:08x}", offset),
+            OffsetBase::Dec => format!("{:08}", offset),
+            OffsetBase::Oct => format!("{:08o}", offset),
+        }
+    }
+
+    fn parse(self, s: &str) -> Option<u64> {
+        let radix = match self {
+            OffsetBase::Hex => 16,
+            OffsetBase::Dec => 10,
+            OffsetBase::Oct => 8,
+        };
+        u64::from_str_radix(s, radix).ok()
+    }
+}
+
+pub(crate) struct HexDump {
+    cols: usize,
+    group: usize,
+    base: OffsetBase,
+    offset: u64,
+    row: Vec<u8>,
+}
+
+impl HexDump {
+    /// A `group` of 0 prints each row as one unbroken run of hex digits.
+    pub(crate) fn new(cols: usize, group: usize, base: OffsetBase) -> Self {
+        HexDump {
+            cols,
+            group: if group == 0 { cols } else { group },
+            base,
+            offset: 0,
+            row: Vec::with_capacity(cols),
+        }
+    }
+
+    /// Dumps everything `input` has, keeping a short last row for the next
+    /// input to fill up.
+    pub(crate) fn dump(&mut self, input: &mut impl Read, out: &mut impl Write) -> Result<()> {
+        let mut buf = [0; 8192];
+        loop {
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            for &byte in &buf[..n] {
+                self.row.push(byte);
+                if self.row.len() == self.cols {
+                    self.write_row(out)?;
+                }
+            }
+        }
+    }
+
+    /// Writes the last, possibly short, row.
+    pub(crate) fn finish(&mut self, out: &mut impl Write) -> Result<()> {
+        if !self.row.is_empty() {
+            self.write_row(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, out: &mut impl Write) -> Result<()> {
+        let mut line = format!("{}: ", self.base.format(self.offset));
+        for i in 0..self.cols {
+            match self.row.get(i) {
+                Some(byte) => line.push_str(&format!("{:02x}", byte)),
+                None => line.push_str("  "),
+            }
+            if (i + 1).is_multiple_of(self.group) && i + 1 < self.cols {
+                line.push(' ');
+            }
+        }
+        line.push_str("  ");
+        line.extend(self.row.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }
+        }));
+        writeln!(out, "{}", line)?;
+
+        self.offset += self.row.len() as u64;
+        self.row.clear();
+        Ok(())
+    }
+}
+
+/// Rebuilds the bytes of an xxd-style dump. Rows are written at their
+/// offsets; a gap since the previous row is filled with zeros.
+pub(crate) struct HexReverse {
+    base: OffsetBase,
+    pos: u64,
+}
+
+impl HexReverse {
+    pub(crate) fn new(base: OffsetBase) -> Self {
+        HexReverse { base, pos: 0 }
+    }
+
+    pub(crate) fn reverse(
+        &mut self,
+        filename: &str,
+        input: &mut impl BufRead,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let mut line = String::new();
+        let mut line_num = 0;
+        while input.read_line(&mut line)? > 0 {
+            line_num += 1;
+            if !line.trim().is_empty() {
+                match parse_row(&line, self.base) {
+                    Some((offset, bytes)) => {
+                        if offset < self.pos {
+                            bail!("{}:{}: offset goes backwards", filename, line_num);
+                        }
+                        let Some(end) = offset.checked_add(bytes.len() as u64) else {
+                            bail!("{}:{}: offset is too large", filename, line_num);
+                        };
+                        // The gap comes from the input, so it is streamed
+                        // rather than allocated.
+                        io::copy(&mut io::repeat(0).take(offset - self.pos), out)?;
+                        out.write_all(&bytes)?;
+                        self.pos = end;
+                    }
+                    None => bail!("{}:{}: not a hex dump line", filename, line_num),
+                }
+            }
+            line.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Splits a row into its offset and bytes. The hex column ends at the first
+/// double space, which separates it from the ASCII column.
+fn parse_row(line: &str, base: OffsetBase) -> Option<(u64, Vec<u8>)> {
+    let (offset, rest) = line.split_once(':')?;
+    let offset = base.parse(offset.trim())?;
+    let rest = rest.trim_end_matches(['\n', '\r']);
+    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+    let hex = rest.split_once("  ").map_or(rest, |(hex, _)| hex);
+
+    let digits: Vec<u8> = hex.bytes().filter(|b| *b != b' ').collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some((offset, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..=255).chain(b"  two spaces  ".iter().copied()).collect();
+        for (cols, group) in [(16, 2), (8, 1), (10, 4), (7, 0)] {
+            let mut dump = Vec::new();
+            let mut hex = HexDump::new(cols, group, OffsetBase::Oct);
+            hex.dump(&mut Cursor::new(&data), &mut dump).unwrap();
+            hex.finish(&mut dump).unwrap();
+
+            let mut bytes = Vec::new();
+            HexReverse::new(OffsetBase::Oct)
+                .reverse("-", &mut Cursor::new(dump), &mut bytes)
+                .unwrap();
+            assert_eq!(bytes, data);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! `--highlight`: syntax highlighting with 24-bit terminal colors.
+//!
+//! The language of a file comes from `--language`, its extension, or its
+//! first line (a shebang or a mode line), in that order.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::as_24_bit_terminal_escaped;
+
+pub(crate) const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// The syntax definitions and the chosen theme, loaded once for all files.
+pub(crate) struct Assets {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+    language: Option<String>,
+}
+
+impl Assets {
+    pub(crate) fn load(theme: &str, language: Option<&str>) -> Result<Self> {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let mut themes = ThemeSet::load_defaults().themes;
+
+        let Some(theme) = themes.remove(theme) else {
+            let names: Vec<_> = themes.keys().map(String::as_str).collect();
+            bail!("unknown theme '{}' (available: {})", theme, names.join(", "));
+        };
+        if let Some(language) = language {
+            if syntaxes.find_syntax_by_token(language).is_none() {
+                bail!("unknown language '{}'", language);
+            }
+        }
+
+        Ok(Assets {
+            syntaxes,
+            theme,
+            language: language.map(str::to_string),
+        })
+    }
+
+    fn syntax(&self, filename: &str, first_line: &str) -> &SyntaxReference {
+        let by_name = || {
+            let path = Path::new(filename);
+            let extension = path.extension().or(path.file_name())?.to_str()?;
+            self.syntaxes.find_syntax_by_extension(extension)
+        };
+
+        match &self.language {
+            Some(language) => self.syntaxes.find_syntax_by_token(language),
+            None => by_name().or_else(|| self.syntaxes.find_syntax_by_first_line(first_line)),
+        }
+        .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+}
+
+/// Highlights the lines of one file, keeping the parser state from one line
+/// to the next.
+pub(crate) struct Highlighter<'a> {
+    assets: &'a Assets,
+    filename: String,
+    lines: Option<HighlightLines<'a>>,
+}
+
+impl<'a> Highlighter<'a> {
+    pub(crate) fn new(assets: &'a Assets, filename: &str) -> Self {
+        Highlighter {
+            assets,
+            filename: filename.to_string(),
+            lines: None,
+        }
+    }
+
+    /// Returns `text` (without its terminator) wrapped in color escapes, or
+    /// `None` if it is not UTF-8 or cannot be parsed; such lines are printed
+    /// as they are.
+    pub(crate) fn highlight(&mut self, text: &[u8]) -> Option<String> {
+        let text = std::str::from_utf8(text).ok()?;
+        let assets = self.assets;
+        let lines = self.lines.get_or_insert_with(|| {
+            HighlightLines::new(assets.syntax(&self.filename, text), &assets.theme)
+        });
+
+        let line = format!("{}\n", text);
+        let ranges = lines.highlight_line(&line, &assets.syntaxes).ok()?;
+        let mut escaped = as_24_bit_terminal_escaped(&ranges, false);
+        if escaped.ends_with('\n') {
+            escaped.pop();
+        }
+        escaped.push_str("\x1b[0m");
+        Some(escaped)
+    }
+}
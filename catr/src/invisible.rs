@@ -0,0 +1,118 @@
+//! `--reveal-invisible` and `--deny-invisible`: find characters that make
+//! text read differently from how it compiles ("Trojan Source"): bidi
+//! controls, zero-width characters, non-breaking spaces, and letters from
+//! another script hiding in an identifier.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'A'..='Z' | 'a'..='z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+            Some(Script::Latin)
+        }
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052F}' => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Characters that are invisible, or invisibly change the text around them.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        // Bidi marks, embeddings, overrides and isolates
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+        // Zero-width characters and the soft hyphen
+        | '\u{00AD}' | '\u{180E}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}'
+        // Non-breaking spaces
+        | '\u{00A0}' | '\u{2007}' | '\u{202F}'
+    )
+}
+
+/// Returns `text` with every suspicious character replaced by a `<U+XXXX>`
+/// marker, along with the characters found. Bytes that are not UTF-8 are
+/// left alone.
+pub(crate) fn reveal(text: &[u8]) -> (Vec<u8>, Vec<char>) {
+    let mut revealed = Vec::with_capacity(text.len());
+    let mut found = Vec::new();
+    for chunk in text.utf8_chunks() {
+        revealed.extend_from_slice(reveal_str(chunk.valid(), &mut found).as_bytes());
+        revealed.extend_from_slice(chunk.invalid());
+    }
+    (revealed, found)
+}
+
+fn reveal_str(text: &str, found: &mut Vec<char>) -> String {
+    let mut revealed = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let word_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if word_len == 0 {
+            if is_invisible(c) {
+                revealed.push_str(&format!("<U+{:04X}>", c as u32));
+                found.push(c);
+            } else {
+                revealed.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let (word, tail) = rest.split_at(word_len);
+        let foreign = foreign_script(word);
+        for c in word.chars() {
+            match script(c) {
+                Some(s) if Some(s) == foreign => {
+                    revealed.push_str(&format!("<U+{:04X}>", c as u32));
+                    found.push(c);
+                }
+                _ => revealed.push(c),
+            }
+        }
+        rest = tail;
+    }
+
+    revealed
+}
+
+/// For an identifier that mixes scripts, the script of its odd letters: the
+/// non-Latin one if it has Latin letters, else whichever did not come first.
+fn foreign_script(word: &str) -> Option<Script> {
+    let mut scripts = word.chars().filter_map(script);
+    let first = scripts.next()?;
+    let other = scripts.find(|&s| s != first)?;
+    match (first, other) {
+        (Script::Latin, other) => Some(other),
+        (first, Script::Latin) => Some(first),
+        (_, other) => Some(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reveal() {
+        let (text, found) = reveal("if x \u{202E}} \u{2066}// admin\u{2069}".as_bytes());
+        assert_eq!(text, b"if x <U+202E>} <U+2066>// admin<U+2069>");
+        assert_eq!(found.len(), 3);
+
+        // Cyrillic "а" in a Latin word, and a zero-width space splitting one.
+        let (text, found) = reveal("p\u{0430}ypal pay\u{200B}pal слово".as_bytes());
+        assert_eq!(text, "p<U+0430>ypal pay<U+200B>pal слово".as_bytes());
+        assert_eq!(found, ['\u{0430}', '\u{200B}']);
+
+        let (text, found) = reveal(b"\xff\xfe\xc2\xa0");
+        assert_eq!(text, b"\xff\xfe<U+00A0>");
+        assert_eq!(found, ['\u{00A0}']);
+    }
+}
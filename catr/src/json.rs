@@ -0,0 +1,677 @@
+//! `--json-pretty`: pretty-print JSON documents and NDJSON streams.
+//!
+//! The input is parsed as it is read and printed as soon as each line of
+//! output is complete, so a large document is never held in memory, except
+//! with `--sort-keys`, which needs each whole top-level value to sort it.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Cursor, Read, Write};
+
+use anyhow::{anyhow, Result};
+
+use crate::cat::{self, Printer};
+
+/// How much of a sorted value is laid out before it is printed.
+const REPLAY_LIMIT: usize = 64 * 1024;
+
+/// How a top-level value is printed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Style {
+    /// Spaces per level; 0 prints each value on one line.
+    pub(crate) indent: usize,
+    pub(crate) sort_keys: bool,
+}
+
+/// What the parser finds, in order.
+enum Event<'a> {
+    Begin(u8),
+    End(u8),
+    Key(&'a [u8]),
+    Scalar(&'a [u8]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    ValueOrEnd,
+    Key,
+    KeyOrEnd,
+    Colon,
+    CommaOrEnd,
+}
+
+/// Checks the grammar one byte at a time, so input can come in any pieces.
+struct Parser {
+    /// The open containers, `{` or `[`.
+    stack: Vec<u8>,
+    expect: Expect,
+    /// The string, number or literal being read.
+    token: Vec<u8>,
+    in_string: bool,
+    /// In a string: after a backslash, or how many hex digits of `\u` are
+    /// still to come.
+    escape: Option<u8>,
+    line: usize,
+    col: usize,
+    /// Where `token` started.
+    start: (usize, usize),
+}
+
+impl Parser {
+    fn new() -> Self {
+        Parser {
+            stack: Vec::new(),
+            expect: Expect::Value,
+            token: Vec::new(),
+            in_string: false,
+            escape: None,
+            line: 1,
+            col: 0,
+            start: (1, 0),
+        }
+    }
+
+    /// Whether everything so far was complete values.
+    fn idle(&self) -> bool {
+        self.stack.is_empty() && self.token.is_empty() && self.expect == Expect::Value
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("{}:{}: {}", self.line, self.col, message)
+    }
+
+    fn start_token(&mut self, b: u8) {
+        self.start = (self.line, self.col);
+        self.token.push(b);
+    }
+
+    fn feed(&mut self, bytes: &[u8], sink: &mut impl FnMut(Event)) -> Result<(), String> {
+        for &b in bytes {
+            if b == b'\n' {
+                self.line += 1;
+                self.col = 0;
+            } else {
+                self.col += 1;
+            }
+            self.byte(b, sink)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, sink: &mut impl FnMut(Event)) -> Result<(), String> {
+        self.end_scalar(sink)?;
+        if self.idle() {
+            Ok(())
+        } else {
+            Err(self.error("unexpected end of input"))
+        }
+    }
+
+    fn byte(&mut self, b: u8, sink: &mut impl FnMut(Event)) -> Result<(), String> {
+        if self.in_string {
+            return self.string_byte(b, sink);
+        }
+        if !self.token.is_empty() {
+            if is_scalar_byte(b) {
+                self.token.push(b);
+                return Ok(());
+            }
+            self.end_scalar(sink)?;
+        }
+
+        match (b, self.expect) {
+            (b' ' | b'\t' | b'\n' | b'\r', _) => {}
+            (b'"', _) if self.expect != Expect::Colon && self.expect != Expect::CommaOrEnd => {
+                self.in_string = true;
+                self.start_token(b);
+            }
+            (b'{' | b'[', Expect::Value | Expect::ValueOrEnd) => {
+                sink(Event::Begin(b));
+                self.stack.push(b);
+                self.expect = if b == b'{' {
+                    Expect::KeyOrEnd
+                } else {
+                    Expect::ValueOrEnd
+                };
+            }
+            (b'}' | b']', Expect::KeyOrEnd | Expect::ValueOrEnd | Expect::CommaOrEnd)
+                if self.stack.last() == Some(&if b == b'}' { b'{' } else { b'[' }) =>
+            {
+                self.stack.pop();
+                sink(Event::End(b));
+                self.after_value();
+            }
+            (b':', Expect::Colon) => self.expect = Expect::Value,
+            (b',', Expect::CommaOrEnd) => {
+                self.expect = if self.stack.last() == Some(&b'{') {
+                    Expect::Key
+                } else {
+                    Expect::Value
+                };
+            }
+            (_, Expect::Value | Expect::ValueOrEnd) if is_scalar_byte(b) => self.start_token(b),
+            _ => return Err(self.error(format!("unexpected '{}'", (b as char).escape_default()))),
+        }
+        Ok(())
+    }
+
+    fn string_byte(&mut self, b: u8, sink: &mut impl FnMut(Event)) -> Result<(), String> {
+        self.token.push(b);
+        match (self.escape, b) {
+            (_, 0x00..=0x1F) => return Err(self.error("control character in string")),
+            (Some(0), b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.escape = None,
+            (Some(0), b'u') => self.escape = Some(4),
+            (Some(0), _) => return Err(self.error("invalid escape in string")),
+            (Some(n), _) if b.is_ascii_hexdigit() => {
+                self.escape = if n > 1 { Some(n - 1) } else { None }
+            }
+            (Some(_), _) => return Err(self.error("invalid \\u escape in string")),
+            (None, b'\\') => self.escape = Some(0),
+            (None, b'"') => {
+                self.in_string = false;
+                if matches!(self.expect, Expect::Key | Expect::KeyOrEnd) {
+                    sink(Event::Key(&self.token));
+                    self.expect = Expect::Colon;
+                } else {
+                    sink(Event::Scalar(&self.token));
+                    self.after_value();
+                }
+                self.token.clear();
+            }
+            (None, _) => {}
+        }
+        Ok(())
+    }
+
+    fn end_scalar(&mut self, sink: &mut impl FnMut(Event)) -> Result<(), String> {
+        if self.token.is_empty() || self.in_string {
+            return Ok(());
+        }
+        if !matches!(&self.token[..], b"true" | b"false" | b"null") && !is_number(&self.token) {
+            let (line, col) = self.start;
+            let token = String::from_utf8_lossy(&self.token);
+            return Err(format!("{}:{}: invalid literal '{}'", line, col, token));
+        }
+        sink(Event::Scalar(&self.token));
+        self.token.clear();
+        self.after_value();
+        Ok(())
+    }
+
+    fn after_value(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::Value
+        } else {
+            Expect::CommaOrEnd
+        };
+    }
+}
+
+fn is_scalar_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.')
+}
+
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`
+fn is_number(token: &[u8]) -> bool {
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut rest = token.strip_prefix(b"-").unwrap_or(token);
+    let n = digits(rest);
+    if n == 0 || (n > 1 && rest[0] == b'0') {
+        return false;
+    }
+    rest = &rest[n..];
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let n = digits(fraction);
+        if n == 0 {
+            return false;
+        }
+        rest = &fraction[n..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        let n = digits(exponent);
+        return n > 0 && n == exponent.len();
+    }
+    rest.is_empty()
+}
+
+/// Lays out the events as indented text.
+struct Writer {
+    indent: usize,
+    depth: usize,
+    /// A container was just opened.
+    fresh: bool,
+    /// A key was just written, so no separator goes before its value.
+    after_key: bool,
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn new(indent: usize) -> Self {
+        Writer {
+            indent,
+            depth: 0,
+            fresh: false,
+            after_key: false,
+            out: Vec::new(),
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Begin(b) => {
+                self.before_value();
+                self.out.push(b);
+                self.depth += 1;
+                self.fresh = true;
+            }
+            Event::End(b) => {
+                self.depth -= 1;
+                if !self.fresh {
+                    self.newline();
+                }
+                self.fresh = false;
+                self.out.push(b);
+                self.end_value();
+            }
+            Event::Key(key) => {
+                self.before_item();
+                self.out.extend_from_slice(key);
+                self.out
+                    .extend_from_slice(if self.indent > 0 { b": " } else { b":" });
+                self.after_key = true;
+            }
+            Event::Scalar(scalar) => {
+                self.before_value();
+                self.out.extend_from_slice(scalar);
+                self.end_value();
+            }
+        }
+    }
+
+    fn before_value(&mut self) {
+        if !std::mem::take(&mut self.after_key) {
+            self.before_item();
+        }
+    }
+
+    fn before_item(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        if !std::mem::take(&mut self.fresh) {
+            self.out.push(b',');
+        }
+        self.newline();
+    }
+
+    fn newline(&mut self) {
+        if self.indent > 0 {
+            self.out.push(b'\n');
+            self.out
+                .resize(self.out.len() + self.depth * self.indent, b' ');
+        }
+    }
+
+    fn end_value(&mut self) {
+        if self.depth == 0 {
+            self.out.push(b'\n');
+        }
+    }
+}
+
+/// A top-level value kept whole for `--sort-keys`.
+enum Node {
+    Scalar(Vec<u8>),
+    Array(Vec<Node>),
+    Object(Vec<(Vec<u8>, Node)>),
+}
+
+// Values can nest deeper than the call stack goes, so nothing here recurses.
+impl Node {
+    fn sort(&mut self) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Scalar(_) => {}
+                Node::Array(items) => stack.extend(items.iter_mut()),
+                Node::Object(entries) => {
+                    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    stack.extend(entries.iter_mut().map(|(_, node)| node));
+                }
+            }
+        }
+    }
+
+    /// Moves the values inside this one onto `stack`.
+    fn take_children(&mut self, stack: &mut Vec<Node>) {
+        match self {
+            Node::Scalar(_) => {}
+            Node::Array(items) => stack.append(items),
+            Node::Object(entries) => stack.extend(entries.drain(..).map(|(_, node)| node)),
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut node) = stack.pop() {
+            node.take_children(&mut stack);
+        }
+    }
+}
+
+/// What is left to write of a sorted value, the next step last.
+enum Step {
+    Node(Node),
+    Key(Vec<u8>),
+    End(u8),
+}
+
+/// Builds each top-level value, then writes it with its keys sorted.
+#[derive(Default)]
+struct Sorter {
+    /// Open containers, each with the key its value will have.
+    stack: Vec<(Node, Option<Vec<u8>>)>,
+    key: Option<Vec<u8>>,
+    /// Sorted values still to be written.
+    sorted: VecDeque<Node>,
+    steps: Vec<Step>,
+}
+
+impl Sorter {
+    fn event(&mut self, event: Event) {
+        let node = match event {
+            Event::Begin(b) => {
+                let node = if b == b'{' {
+                    Node::Object(Vec::new())
+                } else {
+                    Node::Array(Vec::new())
+                };
+                self.stack.push((node, self.key.take()));
+                return;
+            }
+            Event::Key(key) => {
+                self.key = Some(key.to_vec());
+                return;
+            }
+            Event::Scalar(scalar) => Node::Scalar(scalar.to_vec()),
+            Event::End(_) => {
+                let (node, key) = self.stack.pop().expect("balanced");
+                self.key = key;
+                node
+            }
+        };
+        match self.stack.last_mut() {
+            Some((Node::Array(items), _)) => items.push(node),
+            Some((Node::Object(entries), _)) => {
+                entries.push((self.key.take().unwrap_or_default(), node))
+            }
+            _ => {
+                let mut node = node;
+                node.sort();
+                self.sorted.push_back(node);
+            }
+        }
+    }
+
+    /// Writes sorted values until `limit` bytes or more have been added to
+    /// `writer`, so a large value is never laid out whole. Returns whether
+    /// any is left.
+    fn replay(&mut self, writer: &mut Writer, limit: usize) -> bool {
+        let start = writer.out.len();
+        while writer.out.len() - start < limit {
+            let Some(step) = self.steps.pop().or_else(|| self.sorted.pop_front().map(Step::Node))
+            else {
+                return false;
+            };
+            match step {
+                Step::Node(mut node) => match &mut node {
+                    Node::Scalar(scalar) => writer.event(Event::Scalar(scalar)),
+                    Node::Array(items) => {
+                        writer.event(Event::Begin(b'['));
+                        self.steps.push(Step::End(b']'));
+                        self.steps.extend(items.drain(..).rev().map(Step::Node));
+                    }
+                    Node::Object(entries) => {
+                        writer.event(Event::Begin(b'{'));
+                        self.steps.push(Step::End(b'}'));
+                        for (key, node) in entries.drain(..).rev() {
+                            self.steps.push(Step::Node(node));
+                            self.steps.push(Step::Key(key));
+                        }
+                    }
+                },
+                Step::Key(key) => writer.event(Event::Key(&key)),
+                Step::End(b) => writer.event(Event::End(b)),
+            }
+        }
+        true
+    }
+}
+
+/// The parser and the layout of one file.
+struct Formatter {
+    parser: Parser,
+    sorter: Option<Sorter>,
+    writer: Writer,
+}
+
+impl Formatter {
+    fn new(style: Style) -> Self {
+        Formatter {
+            parser: Parser::new(),
+            sorter: style.sort_keys.then(Sorter::default),
+            writer: Writer::new(style.indent),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let (writer, sorter) = (&mut self.writer, &mut self.sorter);
+        self.parser.feed(bytes, &mut |event| match sorter {
+            Some(sorter) => sorter.event(event),
+            None => writer.event(event),
+        })
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        let (writer, sorter) = (&mut self.writer, &mut self.sorter);
+        self.parser.finish(&mut |event| match sorter {
+            Some(sorter) => sorter.event(event),
+            None => writer.event(event),
+        })
+    }
+
+    /// Prints the complete lines written so far, and those of the sorted
+    /// values, a piece at a time.
+    fn print(&mut self, printer: &mut Printer, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            let more = match &mut self.sorter {
+                Some(sorter) => sorter.replay(&mut self.writer, REPLAY_LIMIT),
+                None => false,
+            };
+            let out_buf = &mut self.writer.out;
+            if let Some(end) = out_buf.iter().rposition(|&b| b == b'\n') {
+                for line in out_buf[..=end].split_inclusive(|&b| b == b'\n') {
+                    printer.print(out, line)?;
+                }
+                out_buf.drain(..=end);
+            }
+            if !more {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Ends and prints the line being written, for a value that will not
+    /// be finished.
+    fn end_line(&mut self, printer: &mut Printer, out: &mut impl Write) -> io::Result<()> {
+        if !self.writer.out.is_empty() {
+            self.writer.out.push(b'\n');
+        }
+        self.print(printer, out)
+    }
+}
+
+/// Pretty-prints a file that starts with `{` or `[`; any other file is
+/// printed as it is.
+pub(crate) fn pretty(
+    filename: &str,
+    reader: &mut impl BufRead,
+    style: Style,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> Result<()> {
+    let io_err = |err: io::Error| anyhow!("{}: {}", filename, err);
+
+    let mut leading = Vec::new();
+    loop {
+        let buf = reader.fill_buf().map_err(io_err)?;
+        let spaces = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        leading.extend_from_slice(&buf[..spaces]);
+        let starts = buf.get(spaces).copied();
+        reader.consume(spaces);
+        match starts {
+            Some(b'{' | b'[') => break,
+            None if spaces > 0 => continue,
+            _ => {
+                let mut rest = Cursor::new(leading).chain(reader);
+                return cat::print_lines(&mut rest, printer, out).map_err(io_err);
+            }
+        }
+    }
+
+    let mut formatter = Formatter::new(style);
+    formatter.feed(&leading).expect("whitespace");
+    loop {
+        let buf = reader.fill_buf().map_err(io_err)?;
+        if buf.is_empty() {
+            break;
+        }
+        let n = buf.len();
+        formatter
+            .feed(buf)
+            .map_err(|err| anyhow!("{}:{}", filename, err))?;
+        reader.consume(n);
+        formatter.print(printer, out).map_err(io_err)?;
+    }
+    formatter
+        .finish()
+        .map_err(|err| anyhow!("{}:{}", filename, err))?;
+    formatter.print(printer, out).map_err(io_err)
+}
+
+/// Pretty-prints the values in a file and prints every other line as it
+/// is. A value that spans lines is printed as its lines are read; if it
+/// turns out not to be JSON, the part printed is ended and the line where
+/// that shows is printed as it is. With `--sort-keys` a value is held until
+/// it is complete, and all of its lines are printed as they are if it is
+/// not JSON.
+pub(crate) fn pretty_lenient(
+    reader: &mut impl BufRead,
+    style: Style,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut formatter = Formatter::new(style);
+    // With --sort-keys, the lines of the value being read.
+    let mut held: Vec<Vec<u8>> = Vec::new();
+    // How much was written before the held lines and the current one.
+    let mut kept = 0;
+    let mut line = Vec::new();
+
+    while printer.read_record(reader, &mut line)? > 0 {
+        let first = line.iter().find(|b| !b.is_ascii_whitespace());
+        if formatter.parser.idle() && !matches!(first, Some(b'{' | b'[')) {
+            printer.print(out, &line)?;
+        } else {
+            if held.is_empty() {
+                kept = formatter.writer.out.len();
+            }
+            match formatter.feed(&line) {
+                Ok(()) if formatter.parser.idle() => {
+                    formatter.print(printer, out)?;
+                    held.clear();
+                }
+                Ok(()) if style.sort_keys => held.push(std::mem::take(&mut line)),
+                Ok(()) => formatter.print(printer, out)?,
+                Err(_) => {
+                    formatter.writer.out.truncate(kept);
+                    formatter.end_line(printer, out)?;
+                    for line in held.drain(..) {
+                        printer.print(out, &line)?;
+                    }
+                    printer.print(out, &line)?;
+                    formatter = Formatter::new(style);
+                }
+            }
+        }
+        line.clear();
+    }
+
+    // An unfinished value at the end is not JSON either.
+    if formatter.finish().is_ok() {
+        formatter.print(printer, out)?;
+    } else {
+        if !held.is_empty() {
+            formatter.writer.out.truncate(kept);
+        }
+        formatter.end_line(printer, out)?;
+        for line in held {
+            printer.print(out, &line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(text: &str, indent: usize, sort_keys: bool) -> Result<String, String> {
+        let mut formatter = Formatter::new(Style { indent, sort_keys });
+        // One byte at a time, as if every read returned just that.
+        for b in text.bytes() {
+            formatter.feed(&[b])?;
+        }
+        formatter.finish()?;
+        if let Some(sorter) = &mut formatter.sorter {
+            sorter.replay(&mut formatter.writer, usize::MAX);
+        }
+        Ok(String::from_utf8(std::mem::take(&mut formatter.writer.out)).unwrap())
+    }
+
+    #[test]
+    fn test_format() {
+        let text = r#"{"b":[1,-2.5e3,{}],"a":{"x":"y\"\u00e9"},"c":[]} [true,null]"#;
+        assert_eq!(
+            format(text, 2, false).unwrap(),
+            "{\n  \"b\": [\n    1,\n    -2.5e3,\n    {}\n  ],\n  \"a\": {\n    \"x\": \"y\\\"\\u00e9\"\n  },\n  \"c\": []\n}\n[\n  true,\n  null\n]\n"
+        );
+        assert_eq!(
+            format(text, 0, true).unwrap(),
+            "{\"a\":{\"x\":\"y\\\"\\u00e9\"},\"b\":[1,-2.5e3,{}],\"c\":[]}\n[true,null]\n"
+        );
+        assert_eq!(
+            format("[1,]", 2, false),
+            Err("1:4: unexpected ']'".to_string())
+        );
+        assert_eq!(
+            format("{\"a\" 1}", 2, false),
+            Err("1:6: unexpected '1'".to_string())
+        );
+        assert_eq!(
+            format("[01]", 2, false),
+            Err("1:2: invalid literal '01'".to_string())
+        );
+        assert_eq!(
+            format("{\"a\":\n", 2, false),
+            Err("2:0: unexpected end of input".to_string())
+        );
+    }
+}
@@ -1,8 +1,36 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 
-use std::io::{self, BufReader, BufRead};
-use std::fs::File;
+use std::io::{self, BufReader, BufRead, IsTerminal, Write};
+use std::fs::{self, File};
+
+mod archive;
+mod cat;
+mod control;
+mod decode;
+mod eol;
+mod follow;
+mod hex;
+mod highlight;
+mod invisible;
+mod json;
+mod lint;
+mod pager;
+mod readahead;
+mod redact;
+mod reverse;
+mod timestamp;
+mod truncate;
+
+pub use cat::{Cat, Records};
+pub use eol::Eol;
+pub use timestamp::TimestampMode;
+
+use decode::{DecodeReader, Encoding, OnInvalid};
+use eol::EolReport;
+use hex::OffsetBase;
+use pager::{Output, Paging};
+use readahead::ReadAhead;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -21,35 +49,509 @@ pub struct Args {
         long("number-nonblank"),
     )]
     number_nonblank_lines: bool,
+
+    /// Records end with NUL instead of newline, as with find -print0
+    #[arg(
+        short('z'),
+        long,
+        conflicts_with_all(["hex", "hex_reverse", "report_eol", "eol", "separator"])
+    )]
+    zero_terminated: bool,
+
+    /// Start each line with the name of its file and a colon
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol", "list_archive"]))]
+    with_filename: bool,
+
+    /// Start each line with the time it was read, in strftime FORMAT
+    /// (default "%b %d %H:%M:%S", or "%H:%M:%S" with -s or -i)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args(0..=1),
+        require_equals(true),
+        conflicts_with_all(["hex", "hex_reverse", "report_eol", "list_archive"])
+    )]
+    timestamp: Option<Option<String>>,
+
+    /// With --timestamp, show the time since catr started
+    #[arg(short('s'), long, requires("timestamp"), conflicts_with("incremental"))]
+    since_start: bool,
+
+    /// With --timestamp, show the time since the previous line
+    #[arg(short('i'), long, requires("timestamp"))]
+    incremental: bool,
+
+    /// Keep reading the last file as it grows
+    #[arg(short('f'), long)]
+    follow: bool,
+
+    /// With -f, stop after process PID dies
+    #[arg(
+        long,
+        value_name = "PID",
+        requires("follow"),
+        value_parser(clap::value_parser!(u32).range(1..=i32::MAX as i64))
+    )]
+    pid: Option<u32>,
+
+    /// Print a hex dump (offset, hex bytes and ASCII) of the input
+    #[arg(
+        long,
+        conflicts_with_all(["number_lines", "number_nonblank_lines", "follow"])
+    )]
+    hex: bool,
+
+    /// Rebuild binary data from a hex dump made by --hex or xxd
+    #[arg(long, conflicts_with_all(["hex", "number_lines", "number_nonblank_lines", "follow"]))]
+    hex_reverse: bool,
+
+    /// With --hex, number of bytes per line
+    #[arg(
+        long,
+        value_name = "COLS",
+        default_value("16"),
+        value_parser(clap::value_parser!(u16).range(1..=256))
+    )]
+    cols: u16,
+
+    /// With --hex, number of bytes per group (0 for no grouping)
+    #[arg(long, value_name = "BYTES", default_value("2"))]
+    group: u16,
+
+    /// Number base of the offsets in --hex and --hex-reverse
+    #[arg(long, value_enum, value_name = "BASE", default_value("hex"))]
+    offset_base: OffsetBase,
+
+    /// Convert input from ENC to UTF-8 ("auto" looks for a BOM)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENC",
+        conflicts_with_all(["follow", "hex", "hex_reverse"])
+    )]
+    from_encoding: Option<Encoding>,
+
+    /// With --from-encoding, what to do with undecodable bytes
+    #[arg(long, value_enum, value_name = "POLICY", default_value("replace"))]
+    on_invalid: OnInvalid,
+
+    /// Rewrite line terminators; any of LF, CRLF and CR ends a line
+    #[arg(long, value_enum, value_name = "EOL", default_value("keep"))]
+    eol: Eol,
+
+    /// Print the counts of LF, CRLF and CR terminators of each file instead
+    #[arg(long, conflicts_with_all(["follow", "hex", "hex_reverse"]))]
+    report_eol: bool,
+
+    /// Report trailing whitespace, mixed indentation, a missing final
+    /// newline, a UTF-8 BOM and overlong lines instead, as FILE:LINE:COLUMN;
+    /// fails if any are found
+    #[arg(
+        long,
+        conflicts_with_all([
+            "follow", "hex", "hex_reverse", "report_eol", "list_archive", "zero_terminated"
+        ])
+    )]
+    lint_whitespace: bool,
+
+    /// With --lint-whitespace, the widest a line may be, in terminal cells
+    #[arg(
+        long,
+        value_name = "N",
+        requires("lint_whitespace"),
+        value_parser(clap::value_parser!(u64).range(1..))
+    )]
+    max_width: Option<u64>,
+
+    /// Pretty-print files that hold JSON documents or NDJSON streams; other
+    /// files are printed as they are
+    #[arg(
+        long,
+        conflicts_with_all([
+            "follow", "hex", "hex_reverse", "report_eol", "lint_whitespace", "list_archive",
+            "zero_terminated"
+        ])
+    )]
+    json_pretty: bool,
+
+    /// With --json-pretty, spaces per level (0 for one value per line)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value("2"),
+        requires("json_pretty"),
+        value_parser(clap::value_parser!(u8).range(0..=16))
+    )]
+    indent: u8,
+
+    /// With --json-pretty, sort the keys of objects
+    #[arg(long, requires("json_pretty"))]
+    sort_keys: bool,
+
+    /// With --json-pretty, print lines that are not JSON as they are
+    /// instead of failing
+    #[arg(long, requires("json_pretty"))]
+    lenient: bool,
+
+    /// Print the lines of each file in reverse order, last line first
+    #[arg(
+        long,
+        conflicts_with_all([
+            "follow", "hex", "hex_reverse", "report_eol", "lint_whitespace", "json_pretty"
+        ])
+    )]
+    reverse: bool,
+
+    /// With --reverse, the string that ends each record instead of newline
+    #[arg(
+        long,
+        value_name = "SEP",
+        requires("reverse"),
+        value_parser(clap::builder::NonEmptyStringValueParser::new())
+    )]
+    separator: Option<String>,
+
+    /// Colorize source code by language (only on a terminal, see --color)
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol"]))]
+    highlight: bool,
+
+    /// With --highlight, the language to use instead of detecting it
+    #[arg(long, value_name = "LANG", requires("highlight"))]
+    language: Option<String>,
+
+    /// With --highlight, the color theme
+    #[arg(long, value_name = "THEME", default_value(highlight::DEFAULT_THEME))]
+    theme: String,
+
+    /// Open and read up to N files ahead of the one being printed (0 to
+    /// read each one only when its turn comes)
+    #[arg(long, value_name = "N", default_value("4"))]
+    read_ahead: usize,
+
+    /// When to send output through $PAGER (default "less -R")
+    #[arg(long, value_enum, value_name = "WHEN", default_value("auto"))]
+    paging: Paging,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value("auto"))]
+    color: ColorChoice,
+
+    /// Show bidi controls, zero-width characters, non-breaking spaces and
+    /// mixed-script identifiers as <U+XXXX> markers
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol"]))]
+    reveal_invisible: bool,
+
+    /// Exit with an error if any of the characters of --reveal-invisible
+    /// are found, listing where on stderr
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol"]))]
+    deny_invisible: bool,
+
+    /// Remove terminal escape sequences (CSI, OSC and the like)
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol"]))]
+    strip_ansi: bool,
+
+    /// Replace matches with [REDACTED:kind]; PATTERN is a preset (email,
+    /// ipv4, ipv6, credit-card, jwt, aws-key, bearer, or all) or a regex.
+    /// Repeatable; the counts per kind are printed to stderr
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all(["hex", "hex_reverse", "report_eol", "list_archive"])
+    )]
+    redact: Vec<String>,
+
+    /// Show control characters visibly, e.g. ^[ for ESC (tabs are kept)
+    #[arg(long, conflicts_with_all(["hex", "hex_reverse", "report_eol"]))]
+    escape_control: bool,
+
+    /// Cut lines to at most N terminal cells, not counting the line number
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all(["hex", "hex_reverse", "report_eol"]),
+        value_parser(clap::value_parser!(u64).range(1..))
+    )]
+    truncate: Option<u64>,
+
+    /// With --truncate, what to end a cut line with
+    #[arg(long, value_name = "STR", default_value("…"))]
+    ellipsis: String,
+
+    /// Read member NAME of each tar or zip archive given (also written as
+    /// ARCHIVE:NAME, e.g. bundle.tar.gz:etc/app.conf)
+    #[arg(long, value_name = "NAME", conflicts_with("follow"))]
+    archive_member: Option<String>,
+
+    /// Print the member names of each tar or zip archive instead
+    #[arg(
+        long,
+        conflicts_with_all([
+            "follow", "hex", "hex_reverse", "report_eol", "reverse", "archive_member"
+        ])
+    )]
+    list_archive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ColorChoice {
+    /// Only when stdout is a terminal
+    Auto,
+    Always,
+    Never,
 }
 
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl Args {
+    /// The archive and member that `f` names, if any.
+    fn member<'a>(&'a self, f: &'a str) -> Option<(&'a str, &'a str)> {
+        match &self.archive_member {
+            Some(name) => Some((f, name.as_str())),
+            None => archive::split_spec(f),
+        }
+    }
+
+    /// Whether the file at `file_num` is the one to follow with -f.
+    fn follows(&self, file_num: usize, f: &str) -> bool {
+        self.follow && self.member(f).is_none() && f != "-" && file_num == self.files.len() - 1
+    }
+
+    /// Whether the file at `file_num` is a plain file read from start to
+    /// end, which can be read ahead.
+    fn reads_ahead(&self, file_num: usize, f: &str) -> bool {
+        self.read_ahead > 0
+            && !self.list_archive
+            && !self.reverse
+            && f != "-"
+            && self.member(f).is_none()
+            && !self.follows(file_num, f)
+            && fs::metadata(f).is_ok_and(|meta| meta.is_file())
+    }
+
+    /// Whether some input may arrive slowly (stdin, a pipe or terminal, or
+    /// a file that is followed), so output cannot wait for more of it.
+    fn streams(&self) -> bool {
+        self.follow
+            || self.files.iter().any(|f| {
+                let path = self.member(f).map_or(f.as_str(), |(archive, _)| archive);
+                match path {
+                    "-" => !stdin_is_file(),
+                    path => fs::metadata(path).is_ok_and(|meta| !meta.is_file()),
+                }
+            })
+    }
+}
+
+#[cfg(unix)]
+fn stdin_is_file() -> bool {
+    use std::os::fd::AsFd;
+
+    io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(File::from)
+        .and_then(|file| file.metadata())
+        .is_ok_and(|meta| meta.is_file())
+}
+
+#[cfg(not(unix))]
+fn stdin_is_file() -> bool {
+    false
+}
+
+impl Cat {
+    fn from_args(args: &Args) -> Result<Self> {
+        let mut cat = Cat::new()
+            .number_lines(args.number_lines)
+            .number_nonblank_lines(args.number_nonblank_lines)
+            .eol(args.eol)
+            .zero_terminated(args.zero_terminated)
+            .with_filename(args.with_filename)
+            .strip_ansi(args.strip_ansi)
+            .escape_control(args.escape_control)
+            .reveal_invisible(args.reveal_invisible)
+            .truncate(args.truncate.map(|n| n as usize))
+            .ellipsis(&args.ellipsis);
+        if args.highlight && args.color.enabled() {
+            cat = cat.highlight(&args.theme, args.language.as_deref())?;
+        }
+        if args.deny_invisible {
+            cat = cat.deny_invisible(true).on_invisible(|filename, line_num, found| {
+                let found: Vec<_> = found
+                    .iter()
+                    .map(|c| format!("U+{:04X}", *c as u32))
+                    .collect();
+                eprintln!("{}:{}: {}", filename, line_num, found.join(" "));
+            });
+        }
+        if !args.redact.is_empty() {
+            cat = cat.redact(&args.redact)?;
+        }
+        if let Some(format) = &args.timestamp {
+            let mode = if args.since_start {
+                TimestampMode::SinceStart
+            } else if args.incremental {
+                TimestampMode::Incremental
+            } else {
+                TimestampMode::Clock
+            };
+            cat = cat.timestamp(format.as_deref(), mode)?;
+        }
+        Ok(cat)
+    }
+}
 
 pub fn run(args: Args) -> Result<()> {
     // dbg!(config);
-    for f in args.files {
+    let mut out = Output::new(args.paging, args.streams());
+    let printed = print_files(&args, &mut out);
+    let closed = out.closed();
+    let finished = out.finish();
+    // Whoever reads the output (a pager, or `head`) has all they want.
+    if closed {
+        return Ok(());
+    }
+    printed?;
+    Ok(finished?)
+}
+
+fn print_files(args: &Args, out: &mut Output) -> Result<()> {
+    let mut hex = args.hex.then(|| {
+        hex::HexDump::new(args.cols as usize, args.group as usize, args.offset_base)
+    });
+    let mut unhex = args.hex_reverse.then(|| hex::HexReverse::new(args.offset_base));
+    let cat = Cat::from_args(args)?;
+    let mut whitespace_problems = 0;
+    // Decided once, as a file may change type before it is printed.
+    let reads_ahead: Vec<_> = args
+        .files
+        .iter()
+        .enumerate()
+        .map(|(file_num, f)| args.reads_ahead(file_num, f))
+        .collect();
+    let ahead = args
+        .files
+        .iter()
+        .zip(&reads_ahead)
+        .filter(|(_, &ahead)| ahead)
+        .map(|(f, _)| f.clone())
+        .collect();
+    let mut read_ahead = ReadAhead::new(ahead, args.read_ahead);
+
+    for (file_num, f) in args.files.iter().enumerate() {
         // dbg!(&f);
-        match open(&f) {
+        let mut printer = cat.printer(f);
+
+        if args.list_archive {
+            let prefix = if args.files.len() > 1 { format!("{}:", f) } else { String::new() };
+            if let Err(err) = archive::list(f, &prefix, out) {
+                eprintln!("{}: {}", f, err);
+            }
+            continue;
+        }
+
+        let member = args.member(f);
+
+        if args.follows(file_num, f) {
+            if let Err(err) = follow::follow(f, &mut printer, out, args.pid) {
+                eprintln!("{}: {}", f, err);
+            }
+            continue;
+        }
+
+        let opened = match member {
+            Some((archive, name)) => archive::open(archive, name),
+            None if reads_ahead[file_num] => {
+                read_ahead.next(f).map(|file| file as Box<dyn BufRead>).map_err(Into::into)
+            }
+            None => open(f),
+        };
+        match opened {
             Err(err) => eprintln!("{}: {}", f, err),
-            Ok(file) => {
-                let mut last_num = 0;
-                for (line_num, line) in file.lines().enumerate() {
-                    let line = line?;
-
-                    if args.number_lines {
-                        println!("{:6}\t{}", line_num + 1, line);
-                    } else if args.number_nonblank_lines {
-                        if !line.is_empty() {
-                            last_num += 1;
-                            println!("{:6}\t{}", last_num, line);
+            Ok(mut file) => {
+                if let Some(hex) = &mut hex {
+                    hex.dump(&mut file, out)?;
+                } else if let Some(unhex) = &mut unhex {
+                    unhex.reverse(f, &mut file, out)?;
+                } else {
+                    if let Some(encoding) = args.from_encoding {
+                        file = Box::new(BufReader::new(
+                            DecodeReader::new(file, encoding, args.on_invalid)
+                        ));
+                    }
+                    if args.reverse {
+                        let direct = match (f.as_str(), args.from_encoding, member) {
+                            ("-", _, _) | (_, Some(_), _) | (_, _, Some(_)) => None,
+                            _ => Some(File::open(f)?),
+                        };
+                        let separator = match &args.separator {
+                            Some(separator) => separator.as_str(),
+                            None if args.zero_terminated => "\0",
+                            None => "\n",
+                        };
+                        reverse::seekable(direct, &mut file)
+                            .and_then(|mut file| reverse::print_reversed(
+                                &mut file,
+                                separator.as_bytes(),
+                                &mut printer,
+                                out,
+                            ))
+                            .map_err(|err| anyhow!("{}: {}", f, err))?;
+                    } else if args.report_eol {
+                        let report = EolReport::count(&mut file)
+                            .map_err(|err| anyhow!("{}: {}", f, err))?;
+                        writeln!(out, "{}: {}", f, report)?;
+                    } else if args.lint_whitespace {
+                        let max_width = args.max_width.map(|n| n as usize);
+                        whitespace_problems += lint::lint(f, &mut file, max_width, out)
+                            .map_err(|err| anyhow!("{}: {}", f, err))?;
+                    } else if args.json_pretty {
+                        let style = json::Style {
+                            indent: args.indent as usize,
+                            sort_keys: args.sort_keys,
+                        };
+                        if args.lenient {
+                            json::pretty_lenient(&mut file, style, &mut printer, out)
+                                .map_err(|err| anyhow!("{}: {}", f, err))?;
                         } else {
-                            println!();
+                            json::pretty(f, &mut file, style, &mut printer, out)?;
                         }
                     } else {
-                        println!("{}", line);
+                        cat::print_lines(&mut file, &mut printer, out)
+                            .map_err(|err| anyhow!("{}: {}", f, err))?;
                     }
                 }
             }
         }
+        if file_num + 1 < args.files.len() {
+            printer.end_line(out)?;
+        }
+    }
+
+    if let Some(hex) = &mut hex {
+        hex.finish(out)?;
+    }
+    if !args.redact.is_empty() {
+        let counts: Vec<_> = cat
+            .redacted()
+            .into_iter()
+            .map(|(kind, found)| format!(" {}={}", kind, found))
+            .collect();
+        eprintln!("redacted:{}", counts.concat());
+    }
+    if whitespace_problems > 0 {
+        bail!("{} whitespace problems found", whitespace_problems);
+    }
+    if args.deny_invisible && cat.invisible_found() > 0 {
+        bail!("{} invisible or confusable characters found", cat.invisible_found());
     }
     Ok(())
 }
@@ -80,5 +582,3 @@ fn open(filename: &str) -> Result<Box<dyn BufRead>> {
 //         number_nonblank_lines: args.number_nonblank_lines,
 //     })
 // }
-
-
@@ -0,0 +1,91 @@
+//! `--lint-whitespace`: report whitespace problems as `file:line:column`,
+//! for gating commits.
+
+use std::io::{self, BufRead, Write};
+
+use crate::eol;
+use crate::truncate;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The 1-based column of byte `at`, counting characters.
+fn column(text: &[u8], at: usize) -> usize {
+    let chars: usize = text[..at]
+        .utf8_chunks()
+        .map(|chunk| chunk.valid().chars().count() + chunk.invalid().len())
+        .sum();
+    chars + 1
+}
+
+/// Prints the problems found in one file and returns how many there were.
+pub(crate) fn lint(
+    filename: &str,
+    reader: &mut impl BufRead,
+    max_width: Option<usize>,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut problems = 0;
+    let mut report = |line_num: usize, col: usize, message: &str| {
+        problems += 1;
+        writeln!(out, "{}:{}:{}: {}", filename, line_num, col, message)
+    };
+
+    let mut line = Vec::new();
+    let mut line_num = 0;
+    while eol::read_line(reader, &mut line, true)? > 0 {
+        line_num += 1;
+        let (mut text, terminator) = eol::split_terminator(&line, true);
+
+        if line_num == 1 && text.starts_with(BOM) {
+            report(1, 1, "UTF-8 byte order mark")?;
+            text = &text[BOM.len()..];
+        }
+
+        let indent = text.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+        if text[..indent].contains(&b' ') && text[..indent].contains(&b'\t') {
+            report(line_num, 1, "mixed tabs and spaces in indentation")?;
+        }
+
+        let content = text.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(0, |i| i + 1);
+        if content < text.len() {
+            report(line_num, column(text, content), "trailing whitespace")?;
+        }
+
+        if let Some(max_width) = max_width {
+            let (width, fit) = truncate::measure(text, max_width);
+            if width > max_width {
+                let message = format!("line is {} columns wide (max {})", width, max_width);
+                report(line_num, column(text, fit), &message)?;
+            }
+        }
+
+        if terminator.is_empty() {
+            report(line_num, column(text, text.len()), "no newline at end of file")?;
+        }
+        line.clear();
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let text = "\u{feff}ok\n \tx\nend  \r\nwide wide x\nlast";
+        let mut out = Vec::new();
+        let problems = lint("f", &mut text.as_bytes(), Some(10), &mut out).unwrap();
+
+        assert_eq!(problems, 5);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "f:1:1: UTF-8 byte order mark\n\
+             f:2:1: mixed tabs and spaces in indentation\n\
+             f:3:4: trailing whitespace\n\
+             f:4:11: line is 11 columns wide (max 10)\n\
+             f:5:5: no newline at end of file\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! `--paging`: send output longer than the screen through `$PAGER`.
+//!
+//! In auto mode the output is held back until it is known to be longer than
+//! the screen (or until it is flushed); shorter output goes straight to the
+//! terminal. Output is only held back when all of the input is at hand:
+//! lines read from a pipe or terminal are shown as they come.
+
+use std::env;
+use std::io::{self, BufWriter, IsTerminal, Stdout, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use clap::ValueEnum;
+
+const DEFAULT_PAGER: &str = "less -R";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Paging {
+    /// Only when stdout is a terminal, the input is all files, and the output
+    /// does not fit on the screen
+    Auto,
+    Always,
+    Never,
+}
+
+enum State {
+    Direct(Stdout),
+    /// Output held back to see whether it fits on the screen.
+    Pending(Vec<u8>),
+    Paged(Child, BufWriter<ChildStdin>),
+}
+
+/// Standard output, or the pager's input. A pager that quits early (or a
+/// closed pipe) shows up as `closed`.
+pub(crate) struct Output {
+    state: State,
+    rows: usize,
+    cols: usize,
+    closed: bool,
+}
+
+impl Output {
+    /// `streams` tells that the input may arrive slowly, in which case
+    /// auto mode does not page.
+    pub(crate) fn new(paging: Paging, streams: bool) -> Self {
+        let (rows, cols) = screen_size();
+        let state = match paging {
+            Paging::Always => paged(),
+            Paging::Auto if !streams && io::stdout().is_terminal() => State::Pending(Vec::new()),
+            _ => State::Direct(io::stdout()),
+        };
+        Output { state, rows, cols, closed: false }
+    }
+
+    /// Whether the reader of the output has gone away.
+    pub(crate) fn closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Writes out anything held back and waits for the pager to quit.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.show(State::Direct(io::stdout()))?;
+        match self.state {
+            State::Paged(mut child, stdin) => {
+                let flushed = stdin.into_inner().map_err(|err| err.into_error()).map(drop);
+                child.wait()?;
+                match flushed {
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    flushed => flushed,
+                }
+            }
+            _ => self.flush(),
+        }
+    }
+
+    /// Switches from holding output back to writing it to `state`.
+    fn show(&mut self, state: State) -> io::Result<()> {
+        if let State::Pending(buf) = &mut self.state {
+            let buf = std::mem::take(buf);
+            self.state = state;
+            self.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        if matches!(&result, Err(err) if err.kind() == io::ErrorKind::BrokenPipe) {
+            self.closed = true;
+        }
+        result
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let written = match &mut self.state {
+            State::Direct(stdout) => stdout.write(data),
+            State::Paged(_, stdin) => stdin.write(data),
+            State::Pending(buf) => {
+                buf.extend_from_slice(data);
+                if screen_rows(buf, self.cols) >= self.rows {
+                    self.show(paged())?;
+                }
+                Ok(data.len())
+            }
+        };
+        self.check(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let flushed = match &mut self.state {
+            State::Direct(stdout) => stdout.flush(),
+            State::Paged(_, stdin) => stdin.flush(),
+            // Output that is flushed (by --follow) must be shown now, and
+            // it fits on the screen so far.
+            State::Pending(_) => self.show(State::Direct(io::stdout())).and_then(|_| self.flush()),
+        };
+        self.check(flushed)
+    }
+}
+
+/// How many screen rows `text` takes with lines wrapped at `cols`.
+fn screen_rows(text: &[u8], cols: usize) -> usize {
+    let rows: usize = text
+        .split(|&b| b == b'\n')
+        .map(|line| line.len().div_ceil(cols).max(1))
+        .sum();
+    rows - usize::from(text.ends_with(b"\n"))
+}
+
+/// The pager's input, or stdout if the pager cannot be run.
+fn paged() -> State {
+    match spawn_pager() {
+        Some((child, stdin)) => State::Paged(child, BufWriter::new(stdin)),
+        None => State::Direct(io::stdout()),
+    }
+}
+
+/// Runs `$PAGER` (split on whitespace), or `less -R`.
+fn spawn_pager() -> Option<(Child, ChildStdin)> {
+    let pager = env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty());
+    let pager = pager.as_deref().unwrap_or(DEFAULT_PAGER);
+    let mut words = pager.split_whitespace();
+    let mut child = Command::new(words.next()?)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let stdin = child.stdin.take()?;
+    Some((child, stdin))
+}
+
+#[cfg(unix)]
+fn screen_size() -> (usize, usize) {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    match (ok, size.ws_row, size.ws_col) {
+        (true, rows, cols) if rows > 0 && cols > 0 => (rows as usize, cols as usize),
+        _ => (24, 80),
+    }
+}
+
+#[cfg(not(unix))]
+fn screen_size() -> (usize, usize) {
+    (24, 80)
+}
@@ -0,0 +1,74 @@
+//! Opening and reading the next few files on a background thread while the
+//! current one is written, which hides the latency of many small files on
+//! network filesystems.
+//!
+//! The files come back in the order they were given. Errors are not
+//! reported here but handed over, to be reported where they always were.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// How much of each file is read ahead; the rest is read when it is printed.
+const READ_LIMIT: u64 = 1024 * 1024;
+
+type Opened = io::Result<Box<dyn BufRead + Send>>;
+
+pub(crate) struct ReadAhead {
+    rx: Receiver<(String, Opened)>,
+}
+
+impl ReadAhead {
+    /// Starts reading `files`, at most `depth` of them ahead of [`next`].
+    ///
+    /// [`next`]: ReadAhead::next
+    pub(crate) fn new(files: Vec<String>, depth: usize) -> Self {
+        let (tx, rx) = mpsc::sync_channel(depth.saturating_sub(1));
+        thread::spawn(move || {
+            for filename in files {
+                let opened = read(&filename);
+                if tx.send((filename, opened)).is_err() {
+                    break;
+                }
+            }
+        });
+        ReadAhead { rx }
+    }
+
+    /// The next file, which must be `filename`.
+    pub(crate) fn next(&mut self, filename: &str) -> Opened {
+        let (name, opened) = self.rx.recv().expect("read-ahead thread");
+        debug_assert_eq!(name, filename);
+        opened
+    }
+}
+
+/// Opens a file and reads the start of it. A read error is kept for when
+/// the printing gets there. Anything but a regular file is left unread, as
+/// reading a pipe or terminal ahead would hold back what has arrived.
+fn read(filename: &str) -> Opened {
+    let mut file = File::open(filename)?;
+    if !file.metadata()?.is_file() {
+        return Ok(Box::new(BufReader::new(file)));
+    }
+    let mut head = Vec::new();
+    let rest: Box<dyn Read + Send> = match (&mut file).take(READ_LIMIT).read_to_end(&mut head) {
+        Ok(n) if (n as u64) < READ_LIMIT => Box::new(io::empty()),
+        Ok(_) => Box::new(file),
+        Err(err) => Box::new(Failed(Some(err))),
+    };
+    Ok(Box::new(BufReader::new(Cursor::new(head).chain(rest))))
+}
+
+/// A reader that fails once with the error it was given.
+struct Failed(Option<io::Error>);
+
+impl Read for Failed {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.take() {
+            Some(err) => Err(err),
+            None => Ok(0),
+        }
+    }
+}
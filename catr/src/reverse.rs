@@ -0,0 +1,128 @@
+//! `--reverse`: print the records of a file last to first, like `tac`.
+//!
+//! The file is read backwards in fixed-size chunks, so memory use depends on
+//! the longest record rather than on the size of the file. Input that cannot
+//! be seeked (stdin, pipes, decoded text) is spilled to a temporary file
+//! first.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use crate::cat::Printer;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns `file` if it can be read backwards, or a temporary copy of
+/// `reader` otherwise.
+pub(crate) fn seekable(file: Option<File>, reader: &mut impl Read) -> io::Result<File> {
+    match file {
+        Some(file) if file.metadata()?.is_file() => Ok(file),
+        _ => {
+            let mut spill = tempfile::tempfile()?;
+            io::copy(reader, &mut spill)?;
+            Ok(spill)
+        }
+    }
+}
+
+/// Prints the records of `file` in reverse order. Each record keeps the
+/// separator that ends it, so the output has exactly the bytes of the input.
+pub(crate) fn print_reversed(
+    file: &mut File,
+    separator: &[u8],
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    // The not yet printed bytes just before `pos`, which all belong to
+    // records that started in an earlier chunk.
+    let mut tail = Tail::default();
+
+    while pos > 0 {
+        let len = CHUNK_SIZE.min(pos as usize);
+        pos -= len as u64;
+        let mut chunk = vec![0; len];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut chunk)?;
+        tail.push_front(chunk);
+
+        // Only the new chunk has to be searched, plus enough of the tail
+        // for a separator that starts in the chunk and ends after it.
+        let overlap = (separator.len() - 1).min(tail.len - len);
+        let window = tail.range(0..len + overlap);
+
+        // Everything after a separator is a complete record. The separator
+        // at the very end of the tail ends the record after it, which is
+        // why the search stops one byte short of it.
+        let mut end = tail.len;
+        let mut search = window.len().min(end - 1);
+        while let Some(i) = rfind(&window[..search], separator) {
+            let start = i + separator.len();
+            match window.get(start..end) {
+                Some(record) => printer.print(out, record)?,
+                None => printer.print(out, &tail.range(start..end))?,
+            }
+            end = start;
+            search = i;
+        }
+        tail.truncate(end);
+    }
+
+    if tail.len > 0 {
+        printer.print(out, &tail.range(0..tail.len))?;
+    }
+    Ok(())
+}
+
+/// Bytes kept as the chunks they were read in, so that a long record is
+/// not copied again with every chunk that is added in front of it.
+#[derive(Default)]
+struct Tail {
+    chunks: VecDeque<Vec<u8>>,
+    len: usize,
+}
+
+impl Tail {
+    fn push_front(&mut self, chunk: Vec<u8>) {
+        self.len += chunk.len();
+        self.chunks.push_front(chunk);
+    }
+
+    /// The bytes in `range`, borrowed if they lie in the first chunk.
+    fn range(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        if let Some(bytes) = self.chunks.front().and_then(|first| first.get(range.clone())) {
+            return Cow::Borrowed(bytes);
+        }
+        let mut bytes = Vec::with_capacity(range.len());
+        let mut at = 0;
+        for chunk in &self.chunks {
+            let from = range.start.saturating_sub(at).min(chunk.len());
+            let to = range.end.saturating_sub(at).min(chunk.len());
+            bytes.extend_from_slice(&chunk[from..to]);
+            at += chunk.len();
+            if at >= range.end {
+                break;
+            }
+        }
+        Cow::Owned(bytes)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let mut at = 0;
+        self.chunks.retain_mut(|chunk| {
+            let keep = len.saturating_sub(at).min(chunk.len());
+            at += chunk.len();
+            chunk.truncate(keep);
+            keep > 0
+        });
+        self.len = len;
+    }
+}
+
+/// Finds the start of the last occurrence of `needle` in `haystack`.
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
@@ -0,0 +1,82 @@
+//! `--timestamp`: prefix each line with the moment it was read, like
+//! moreutils `ts`.
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+/// `ts` formats times of day like this.
+const CLOCK_FORMAT: &str = "%b %d %H:%M:%S";
+/// And elapsed times like this.
+const ELAPSED_FORMAT: &str = "%H:%M:%S";
+
+/// What the timestamps of [`Cat::timestamp`] measure.
+///
+/// [`Cat::timestamp`]: crate::Cat::timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampMode {
+    /// The local time of day
+    Clock,
+    /// Time since catr started
+    SinceStart,
+    /// Time since the previous line
+    Incremental,
+}
+
+/// Makes the timestamps for all lines of all files.
+pub(crate) struct Timestamper {
+    items: Vec<Item<'static>>,
+    mode: TimestampMode,
+    start: Instant,
+    last: Cell<Instant>,
+}
+
+impl Timestamper {
+    /// Parses a strftime `format`; without one, uses the format of `ts`.
+    pub(crate) fn new(format: Option<&str>, mode: TimestampMode) -> Result<Self> {
+        let format = format.unwrap_or(match mode {
+            TimestampMode::Clock => CLOCK_FORMAT,
+            _ => ELAPSED_FORMAT,
+        });
+        let items = match StrftimeItems::new(format).parse_to_owned() {
+            Ok(items) if !items.contains(&Item::Error) => items,
+            _ => bail!("invalid timestamp format '{}'", format),
+        };
+
+        let now = Instant::now();
+        Ok(Timestamper { items, mode, start: now, last: Cell::new(now) })
+    }
+
+    /// The timestamp for a line read now.
+    pub(crate) fn stamp(&self) -> impl fmt::Display + '_ {
+        let now = Instant::now();
+        let elapsed = |since: Instant| {
+            // Elapsed times are shown as times of day on 1 January 1970 UTC.
+            DateTime::UNIX_EPOCH + (now - since).min(Duration::from_secs(u32::MAX as u64))
+        };
+        let time = match self.mode {
+            TimestampMode::Clock => Local::now().fixed_offset(),
+            TimestampMode::SinceStart => elapsed(self.start).fixed_offset(),
+            TimestampMode::Incremental => elapsed(self.last.get()).fixed_offset(),
+        };
+        self.last.set(now);
+        time.format_with_items(self.items.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp() {
+        let timestamper = Timestamper::new(Some("%H:%M"), TimestampMode::SinceStart);
+        assert_eq!(timestamper.unwrap().stamp().to_string(), "00:00");
+
+        assert!(Timestamper::new(Some("%Q"), TimestampMode::Clock).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! `--truncate`: cut lines to a number of terminal cells.
+//!
+//! Lines are cut between grapheme clusters, tabs count up to the next tab
+//! stop, and color escapes (as written by `--highlight`) take no room.
+
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const TAB_WIDTH: usize = 8;
+
+enum Token<'a> {
+    /// A color or other CSI escape sequence.
+    Escape(&'a [u8]),
+    Grapheme(&'a str),
+    /// A byte that is not part of any UTF-8 sequence.
+    Invalid(u8),
+}
+
+impl Token<'_> {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Token::Escape(bytes) => bytes,
+            Token::Grapheme(s) => s.as_bytes(),
+            Token::Invalid(b) => std::slice::from_ref(b),
+        }
+    }
+
+    /// The cells this token takes when it starts at column `col`.
+    fn width(&self, col: usize) -> usize {
+        match self {
+            Token::Escape(_) => 0,
+            Token::Grapheme("\t") => TAB_WIDTH - col % TAB_WIDTH,
+            Token::Grapheme(g) => g.width(),
+            // Terminals show a replacement character.
+            Token::Invalid(_) => 1,
+        }
+    }
+}
+
+fn tokens(text: &[u8]) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for chunk in text.utf8_chunks() {
+        let mut rest = chunk.valid();
+        while !rest.is_empty() {
+            let plain = rest.find("\x1b[").unwrap_or(rest.len());
+            tokens.extend(rest[..plain].graphemes(true).map(Token::Grapheme));
+            rest = &rest[plain..];
+            if !rest.is_empty() {
+                let params = rest[2..].bytes().take_while(|b| (0x20..=0x3F).contains(b)).count();
+                // A sequence ends in an ASCII final byte; without one, the
+                // "\x1b[" is ordinary text.
+                let len = match rest.as_bytes().get(2 + params) {
+                    Some(0x40..=0x7E) => 2 + params + 1,
+                    _ => {
+                        tokens.extend(rest[..2].graphemes(true).map(Token::Grapheme));
+                        rest = &rest[2..];
+                        continue;
+                    }
+                };
+                tokens.push(Token::Escape(&rest.as_bytes()[..len]));
+                rest = &rest[len..];
+            }
+        }
+        tokens.extend(chunk.invalid().iter().map(|&b| Token::Invalid(b)));
+    }
+    tokens
+}
+
+/// Returns the width of `text` in cells, and how many of its bytes fit in
+/// `width` cells.
+pub(crate) fn measure(text: &[u8], width: usize) -> (usize, usize) {
+    let mut col = 0;
+    let mut fit = 0;
+    for token in tokens(text) {
+        col += token.width(col);
+        if col <= width {
+            fit += token.bytes().len();
+        }
+    }
+    (col, fit)
+}
+
+/// Returns `text` cut to at most `width` cells, ending in `ellipsis` when
+/// anything was cut.
+pub(crate) fn truncate<'a>(text: &'a [u8], width: usize, ellipsis: &str) -> Cow<'a, [u8]> {
+    let tokens = tokens(text);
+    let mut col = 0;
+    for token in &tokens {
+        col += token.width(col);
+    }
+    if col <= width {
+        return Cow::Borrowed(text);
+    }
+
+    let ellipsis = if ellipsis.width() <= width { ellipsis } else { "" };
+    let limit = width - ellipsis.width();
+    let mut cut = Vec::with_capacity(text.len().min(limit * 4));
+    let mut col = 0;
+    let mut colored = false;
+    for token in &tokens {
+        let next = col + token.width(col);
+        if next > limit {
+            break;
+        }
+        colored |= matches!(token, Token::Escape(_));
+        cut.extend_from_slice(token.bytes());
+        col = next;
+    }
+    cut.extend_from_slice(ellipsis.as_bytes());
+    if colored {
+        cut.extend_from_slice(b"\x1b[0m");
+    }
+    Cow::Owned(cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate(b"short", 5, "..."), &b"short"[..]);
+        assert_eq!(truncate(b"longer line", 8, "..."), &b"longe..."[..]);
+        // Wide CJK characters are never split, even if a cell is left over.
+        assert_eq!(truncate("中文字符".as_bytes(), 5, ""), "中文".as_bytes());
+        // "e" plus a combining accent is one cell and one cluster.
+        let accented = "e\u{301}e\u{301}e\u{301}".as_bytes();
+        assert_eq!(truncate(accented, 2, ""), "e\u{301}e\u{301}".as_bytes());
+        // A tab at column 1 reaches column 8.
+        assert_eq!(truncate(b"a\tbc", 9, ""), &b"a\tb"[..]);
+        assert_eq!(truncate(b"\x1b[31mred\x1b[0m", 2, "~"), &b"\x1b[31mr~\x1b[0m"[..]);
+    }
+
+    #[test]
+    fn test_escape_without_final_byte() {
+        // A multibyte character after "\x1b[" is text, not the end of an escape.
+        let text = "\x1b[中文字符很长".as_bytes();
+        assert_eq!(truncate(text, 4, ""), "\x1b[中".as_bytes());
+        assert_eq!(measure(text, 4), (14, "\x1b[中".len()));
+        assert_eq!(measure(b"\x1b[31", 1), (4, 1));
+    }
+}
@@ -75,6 +75,31 @@ fn skip_bad_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn crlf_unterminated_as_is() -> Result<()> {
+    // Lines are written as read: CRLF stays, and no newline is added at the
+    // end, so files are joined byte for byte, as cat does.
+    let crlf = "tests/inputs/crlf-unterminated.txt";
+
+    Command::cargo_bin(PRG)?
+        .args([crlf, crlf])
+        .assert()
+        .success()
+        .stdout("a\r\nba\r\nb");
+    Command::cargo_bin(PRG)?
+        .args(["-n", crlf])
+        .assert()
+        .success()
+        .stdout("     1\ta\r\n     2\tb");
+    Command::cargo_bin(PRG)?
+        .write_stdin("a\r\nb")
+        .assert()
+        .success()
+        .stdout("a\r\nb");
+
+    Ok(())
+}
+
 #[test]
 fn bustle() -> Result<()> {
     run(
@@ -194,4 +219,964 @@ fn all_n() -> Result<()> {
 #[test]
 fn all_b() -> Result<()> {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
-}
\ No newline at end of file
+}
+// --------------------------------------------------
+#[test]
+fn pid_requires_follow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--pid", "1", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--follow"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pid_must_name_a_process() -> Result<()> {
+    // kill(0, 0) would check catr's own process group, which never dies.
+    for pid in ["0", "2147483648"] {
+        Command::cargo_bin(PRG)?
+            .args(["-f", "--pid", pid, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appended_lines() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("log");
+    fs::write(&log, "one\n")?;
+
+    // catr exits once this process is gone.
+    let mut writer = std::process::Command::new("sleep").arg("2").spawn()?;
+    let catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-n", "-f", "--pid", &writer.id().to_string()])
+        .arg(&log)
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let mut file = fs::OpenOptions::new().append(true).open(&log)?;
+    std::io::Write::write_all(&mut file, b"two\nthr")?;
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    std::io::Write::write_all(&mut file, b"ee\n")?;
+
+    writer.wait()?;
+    let output = catr.wait_with_output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "     1\tone\n     2\ttwo\n     3\tthree\n",
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_b_twice() -> Result<()> {
+    let mixed = "tests/inputs/mixed.txt";
+    let once = "     1\tunix\n     2\tdos\r\n\r\n     3\tmac\rlast";
+
+    Command::cargo_bin(PRG)?
+        .args(["-b", mixed, mixed])
+        .assert()
+        .success()
+        .stdout(format!("{once}\n{once}"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_hex() -> Result<()> {
+    run(&["--hex", FOX, SPIDERS, BUSTLE], "tests/expected/all.hex.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_hex_cols_group_dec() -> Result<()> {
+    run(
+        &["--hex", "--cols", "8", "--group", "1", "--offset-base", "dec", SPIDERS],
+        "tests/expected/spiders.txt.hex.c8.g1.d.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_hex_reverse() -> Result<()> {
+    let expected = [FOX, SPIDERS, BUSTLE]
+        .iter()
+        .map(fs::read)
+        .collect::<std::io::Result<Vec<_>>>()?
+        .concat();
+
+    Command::cargo_bin(PRG)?
+        .args(["--hex-reverse", "tests/expected/all.hex.out"])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_reverse_bad_dump() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--hex-reverse", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a hex dump line"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_reverse_huge_gap_streams() -> Result<()> {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .arg("--hex-reverse")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    catr.stdin.take().unwrap().write_all(b"7fffffffffffffff: 00\n")?;
+
+    let mut start = vec![1; 1 << 20];
+    catr.stdout.take().unwrap().read_exact(&mut start)?;
+    assert!(start.iter().all(|&b| b == 0));
+    // Closing the pipe has stopped it already, unless it is still writing.
+    let _ = catr.kill();
+    catr.wait()?;
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_utf16le_auto() -> Result<()> {
+    run(
+        &["--from-encoding", "auto", "tests/inputs/the-bustle.utf16le.txt"],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_utf32be_n() -> Result<()> {
+    run(
+        &["-n", "--from-encoding", "utf-32be", "tests/inputs/the-bustle.utf32be.txt"],
+        "tests/expected/the-bustle.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn shift_jis() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "shift_jis", "tests/inputs/sjis.txt"])
+        .assert()
+        .success()
+        .stdout("こんにちは、世界\nカタカナ\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "latin1"])
+        .write_stdin(b"caf\xe9\n".to_vec())
+        .assert()
+        .success()
+        .stdout("café\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf16_replace() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "utf-16le", "tests/inputs/bad.utf16le.txt"])
+        .assert()
+        .success()
+        .stdout("ok\n\u{FFFD}x\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf16_error() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--from-encoding",
+            "utf-16le",
+            "--on-invalid",
+            "error",
+            "tests/inputs/bad.utf16le.txt",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid UTF-16LE data at byte 6"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_without_bom_is_utf8() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "auto", "--on-invalid", "error"])
+        .write_stdin(b"caf\xe9\n".to_vec())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid UTF-8 data at byte 3"));
+
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "auto"])
+        .write_stdin(b"caf\xe9\n".to_vec())
+        .assert()
+        .success()
+        .stdout("caf\u{FFFD}\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_lf_b() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--eol", "lf", "-b", "tests/inputs/mixed.txt"])
+        .assert()
+        .success()
+        .stdout("     1\tunix\n     2\tdos\n\n     3\tmac\n     4\tlast");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_crlf_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--eol", "crlf"])
+        .write_stdin(fs::read("tests/inputs/mixed.txt")?)
+        .assert()
+        .success()
+        .stdout("unix\r\ndos\r\n\r\nmac\r\nlast");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_keep() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--eol", "keep", "tests/inputs/mixed.txt"])
+        .assert()
+        .success()
+        .stdout(fs::read("tests/inputs/mixed.txt")?);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn report_eol() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--report-eol", "tests/inputs/mixed.txt", FOX, EMPTY])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/mixed.txt: lf=1 crlf=2 cr=1 final-newline=no\n\
+             tests/inputs/fox.txt: lf=1 crlf=0 cr=0 final-newline=yes\n\
+             tests/inputs/empty.txt: lf=0 crlf=0 cr=0 final-newline=yes\n",
+        );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_reverse() -> Result<()> {
+    run(&["--reverse", BUSTLE], "tests/expected/the-bustle.txt.reverse.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_reverse_stdin() -> Result<()> {
+    run_stdin(
+        BUSTLE,
+        &["--reverse", "-"],
+        "tests/expected/the-bustle.txt.reverse.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn long_records_reverse() -> Result<()> {
+    // Records longer than the 64 KiB chunks, with the separators falling
+    // across chunk boundaries.
+    let records: Vec<String> = [200_000, 3, 131_071, 65_532]
+        .iter()
+        .enumerate()
+        .map(|(i, &len)| format!("{}<->", char::from(b'a' + i as u8).to_string().repeat(len)))
+        .collect();
+    let input = records.concat();
+    let expected: String = records.iter().rev().map(String::as_str).collect();
+
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "--separator", "<->"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_reverse_n() -> Result<()> {
+    run(
+        &["--reverse", "-n", SPIDERS],
+        "tests/expected/spiders.txt.reverse.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse_separator_no_final() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "--separator", ", "])
+        .write_stdin("one, two, three")
+        .assert()
+        .success()
+        .stdout("threetwo, one, ");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_plain_when_piped() -> Result<()> {
+    run(&["--highlight", "-n", FOX], "tests/expected/fox.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_color_always() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color", "always", "--language", "rust"])
+        .write_stdin("fn main() {}\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("\x1b[38;2;"))
+        .stdout(predicate::str::ends_with("\x1b[0m\n"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_shebang() -> Result<()> {
+    let plain = "#!/bin/sh\necho hi\n";
+    let output = Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color", "always"])
+        .write_stdin(plain)
+        .output()?;
+
+    assert!(output.status.success());
+    let colored = String::from_utf8(output.stdout)?;
+    assert_ne!(colored, plain);
+    assert!(colored.contains("echo"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_bad_theme() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color", "always", "--theme", "nope", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown theme 'nope'"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn trojan_reveal_n() -> Result<()> {
+    run(
+        &["--reveal-invisible", "-n", "tests/inputs/trojan.txt"],
+        "tests/expected/trojan.txt.reveal.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn trojan_deny() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--deny-invisible", "tests/inputs/trojan.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "tests/inputs/trojan.txt:1: U+202E U+2066 U+2069\n\
+             tests/inputs/trojan.txt:3: U+0430\n",
+        ));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn clean_deny() -> Result<()> {
+    run(&["--deny-invisible", FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ansi_strip() -> Result<()> {
+    run(
+        &["--strip-ansi", "tests/inputs/ansi.txt"],
+        "tests/expected/ansi.txt.strip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ansi_escape() -> Result<()> {
+    run(
+        &["--escape-control", "tests/inputs/ansi.txt"],
+        "tests/expected/ansi.txt.escape.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ansi_strip_escape_n() -> Result<()> {
+    run(
+        &["--strip-ansi", "--escape-control", "-n", "tests/inputs/ansi.txt"],
+        "tests/expected/ansi.txt.strip.escape.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_truncate_n() -> Result<()> {
+    run(
+        &["--truncate", "12", "-n", BUSTLE],
+        "tests/expected/the-bustle.txt.truncate12.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn truncate_wide_chars() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--truncate", "6", "--ellipsis", ">"])
+        .write_stdin("汉字汉字汉字\nshort\n")
+        .assert()
+        .success()
+        .stdout("汉字>\nshort\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn tar_gz_member_n() -> Result<()> {
+    run(
+        &["-n", "tests/inputs/bundle.tar.gz:README.md"],
+        "tests/expected/spiders.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn zip_archive_member() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--archive-member", "./etc/app.conf", "tests/inputs/release.zip"])
+        .assert()
+        .success()
+        .stdout("The quick brown fox jumps over the lazy dog.\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn missing_archive_member() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/release.zip:nope", FOX])
+        .assert()
+        .success()
+        .stderr("tests/inputs/release.zip:nope: no member 'nope'\n")
+        .stdout("The quick brown fox jumps over the lazy dog.\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn list_archives() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--list-archive", "tests/inputs/bundle.tar.gz", "tests/inputs/release.zip"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/bundle.tar.gz:README.md\n\
+             tests/inputs/bundle.tar.gz:etc/\n\
+             tests/inputs/bundle.tar.gz:etc/app.conf\n\
+             tests/inputs/release.zip:README.md\n\
+             tests/inputs/release.zip:etc/app.conf\n",
+        );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated_b() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-b"])
+        .write_stdin("new\nline\0\0last")
+        .assert()
+        .success()
+        .stdout("     1\tnew\nline\0\0     2\tlast");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated_reverse_n() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "--reverse", "-n"])
+        .write_stdin("a\0b\nc\0")
+        .assert()
+        .success()
+        .stdout("     1\tb\nc\0     2\ta\0");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn with_filename_n() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--with-filename", "-n", FOX, "-"])
+        .write_stdin("piped\n")
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/fox.txt:     1\tThe quick brown fox jumps over the lazy dog.\n\
+             -:     1\tpiped\n",
+        );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn timestamp_since_start() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--timestamp=[%H:%M]", "-s", "--with-filename"])
+        .write_stdin("one\ntwo\n")
+        .assert()
+        .success()
+        .stdout("[00:00] -:one\n[00:00] -:two\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn timestamp_bad_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--timestamp=%Q", FOX])
+        .assert()
+        .failure()
+        .stderr("Error: invalid timestamp format '%Q'\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn secrets_redact_all_n() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/secrets.log.redact.n.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--redact", "all", "-n", "tests/inputs/secrets.log"])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr("redacted: jwt=1 bearer=0 aws-key=1 email=1 credit-card=1 ipv6=1 ipv4=1\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn redact_regex_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--redact", "email", "--redact", "token=[a-z0-9]+"])
+        .write_stdin("a@b.io token=abc1\ntoken=x a@b.io\n")
+        .assert()
+        .success()
+        .stdout("[REDACTED:email] [REDACTED:regex]\n[REDACTED:regex] [REDACTED:email]\n")
+        .stderr("redacted: email=2 regex=2\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn lint_whitespace_max_width() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--lint-whitespace", "--max-width", "40", "tests/inputs/whitespace.txt", FOX])
+        .assert()
+        .failure()
+        .stdout(
+            "tests/inputs/whitespace.txt:2:15: trailing whitespace\n\
+             tests/inputs/whitespace.txt:3:1: mixed tabs and spaces in indentation\n\
+             tests/inputs/whitespace.txt:4:41: line is 50 columns wide (max 40)\n\
+             tests/inputs/whitespace.txt:5:2: no newline at end of file\n\
+             tests/inputs/fox.txt:1:41: line is 44 columns wide (max 40)\n",
+        )
+        .stderr("Error: 5 whitespace problems found\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn lint_whitespace_clean() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--lint-whitespace", FOX, SPIDERS])
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_always_pager_cat() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/spiders.txt.n.out")?;
+    Command::cargo_bin(PRG)?
+        .env("PAGER", "cat")
+        .args(["--paging=always", "-n", SPIDERS])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_pager_quits_early() -> Result<()> {
+    let input = "line\n".repeat(200_000);
+    Command::cargo_bin(PRG)?
+        .env("PAGER", "head -n 2")
+        .args(["--paging=always", "-n"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("     1\tline\n     2\tline\n")
+        .stderr("");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+/// A pseudo-terminal, as (controller, terminal).
+#[cfg(unix)]
+fn pty() -> Result<(fs::File, fs::File)> {
+    use std::os::fd::FromRawFd;
+
+    let (mut controller, mut terminal) = (0, 0);
+    let null = std::ptr::null_mut();
+    let ok = unsafe {
+        libc::openpty(&mut controller, &mut terminal, null, null as *const _, null as *const _)
+    };
+    anyhow::ensure!(ok == 0, "openpty: {}", std::io::Error::last_os_error());
+    Ok(unsafe { (fs::File::from_raw_fd(controller), fs::File::from_raw_fd(terminal)) })
+}
+
+/// Reads what is shown on the terminal until `wanted` appears, the
+/// terminal is closed, or `timeout` passes.
+#[cfg(unix)]
+fn read_screen(controller: &mut fs::File, wanted: &str, timeout: std::time::Duration) -> String {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+
+    let deadline = std::time::Instant::now() + timeout;
+    let mut screen = Vec::new();
+    while !String::from_utf8_lossy(&screen).contains(wanted) {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        let mut poll = libc::pollfd { fd: controller.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        if unsafe { libc::poll(&mut poll, 1, left.as_millis() as i32) } <= 0 {
+            break;
+        }
+        let mut buf = [0; 4096];
+        match controller.read(&mut buf) {
+            Ok(n) if n > 0 => screen.extend_from_slice(&buf[..n]),
+            _ => break,
+        }
+    }
+    String::from_utf8_lossy(&screen).into_owned()
+}
+
+#[cfg(unix)]
+#[test]
+fn paging_auto_shows_streamed_stdin_at_once() -> Result<()> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let (mut controller, terminal) = pty()?;
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .env("PAGER", "sed s/^/paged:/")
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stdout(terminal.try_clone()?)
+        .spawn()?;
+    drop(terminal);
+
+    let mut stdin = catr.stdin.take().unwrap();
+    stdin.write_all(b"one\n")?;
+    stdin.flush()?;
+    let screen = read_screen(&mut controller, "one", std::time::Duration::from_secs(5));
+    drop(stdin);
+    catr.wait()?;
+    assert!(screen.contains("     1\tone"), "not shown before EOF: {:?}", screen);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn paging_auto_pages_long_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let long = dir.path().join("long");
+    fs::write(&long, "line\n".repeat(200))?;
+
+    let (mut controller, terminal) = pty()?;
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .env("PAGER", "sed s/^/paged:/")
+        .arg(&long)
+        .stdin(std::process::Stdio::null())
+        .stdout(terminal.try_clone()?)
+        .spawn()?;
+    drop(terminal);
+
+    let screen = read_screen(&mut controller, "paged:line", std::time::Duration::from_secs(5));
+    catr.wait()?;
+    assert!(screen.contains("paged:line"), "not paged: {:?}", screen);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn closed_stdout_is_not_an_error() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let big = dir.path().join("big");
+    fs::write(&big, "line\n".repeat(200_000))?;
+
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .arg(&big)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let mut first = [0; 5];
+    std::io::Read::read_exact(catr.stdout.as_mut().unwrap(), &mut first)?;
+    drop(catr.stdout.take());
+
+    let output = catr.wait_with_output()?;
+    assert!(output.status.success());
+    assert_eq!(&first, b"line\n");
+    assert_eq!(String::from_utf8(output.stderr)?, "");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn read_ahead_keeps_order() -> Result<()> {
+    let bad = get_bad_file();
+    let fox = fs::read_to_string(FOX)?;
+    let spiders = fs::read_to_string(SPIDERS)?;
+    Command::cargo_bin(PRG)?
+        .args(["--read-ahead", "1", SPIDERS, &bad, "-", FOX, SPIDERS])
+        .write_stdin("piped\n")
+        .assert()
+        .success()
+        .stdout(format!("{}piped\n{}{}", spiders, fox, spiders))
+        .stderr(predicate::str::is_match(format!("^{}: .* [(]os error 2[)]\n$", bad))?);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn read_ahead_skips_fifos() -> Result<()> {
+    use std::io::{BufRead, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir()?;
+    let fifo = dir.path().join("fifo");
+    let path = std::ffi::CString::new(fifo.as_os_str().as_bytes())?;
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["--read-ahead", "4", FOX])
+        .arg(&fifo)
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdout = std::io::BufReader::new(catr.stdout.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for _ in 0..2 {
+            let mut line = String::new();
+            let _ = stdout.read_line(&mut line);
+            let _ = tx.send(line);
+        }
+    });
+
+    // The writer stays open until the first line has come through.
+    let mut writer = fs::OpenOptions::new().write(true).open(&fifo)?;
+    writer.write_all(b"one\n")?;
+    let fox = rx.recv_timeout(Duration::from_secs(5));
+    let one = rx.recv_timeout(Duration::from_secs(5));
+    drop(writer);
+    catr.wait()?;
+    assert_eq!(fox?, fs::read_to_string(FOX)?);
+    assert_eq!(one?, "one\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_pretty_lenient_n() -> Result<()> {
+    run(
+        &["--json-pretty", "--lenient", "-n", "tests/inputs/logs.ndjson"],
+        "tests/expected/logs.ndjson.lenient.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_pretty_strict_fails() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--json-pretty", "tests/inputs/logs.ndjson"])
+        .assert()
+        .failure()
+        .stderr("Error: tests/inputs/logs.ndjson:2:1: invalid literal 'plain'\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_pretty_sort_keys_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--json-pretty", "--sort-keys", "--indent", "0", "-", FOX])
+        .write_stdin("  {\"b\": [1, {\"d\": 0, \"c\": null}],\n\"a\": \"x\"}\n[]")
+        .assert()
+        .success()
+        .stdout(
+            "{\"a\":\"x\",\"b\":[1,{\"c\":null,\"d\":0}]}\n[]\n\
+             The quick brown fox jumps over the lazy dog.\n",
+        );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_pretty_sort_keys_deep() -> Result<()> {
+    let deep = format!("{}{}\n", "[".repeat(200_000), "]".repeat(200_000));
+    Command::cargo_bin(PRG)?
+        .args(["--json-pretty", "--sort-keys", "--indent", "0"])
+        .write_stdin(deep.clone())
+        .assert()
+        .success()
+        .stdout(deep);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_pretty_lenient_broken_value() -> Result<()> {
+    let input = "{\"a\":1,\n\"b\":\nnot json\n{\"c\":2}\n";
+    Command::cargo_bin(PRG)?
+        .args(["--json-pretty", "--lenient"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("{\n  \"a\": 1,\n  \"b\": \nnot json\n{\n  \"c\": 2\n}\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--json-pretty", "--lenient", "--sort-keys"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("{\"a\":1,\n\"b\":\nnot json\n{\n  \"c\": 2\n}\n");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn json_pretty_lenient_prints_as_it_reads() -> Result<()> {
+    use std::io::{BufRead, Write};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let mut catr = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["--json-pretty", "--lenient"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdout = std::io::BufReader::new(catr.stdout.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let _ = stdout.read_line(&mut line);
+        let _ = tx.send(line);
+    });
+
+    // The value is still open when its first line must show.
+    let mut stdin = catr.stdin.take().unwrap();
+    stdin.write_all(b"[\n1,\n")?;
+    stdin.flush()?;
+    let first = rx.recv_timeout(Duration::from_secs(5));
+    drop(stdin);
+    catr.wait()?;
+    assert_eq!(first?, "[\n");
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! `--chars` and `--graphemes`: print the first NUM characters (Unicode
+//! scalar values) or grapheme clusters, never cutting a UTF-8 sequence in
+//! two the way `-c` does.
+
+use std::io::{self, BufRead, Write};
+use std::str;
+
+use clap::ValueEnum;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What to do with bytes that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnInvalid {
+    /// Print U+FFFD for each invalid sequence, counting it as one character
+    Replace,
+    /// Print the invalid bytes as they are, each sequence counting as one character
+    Keep,
+    /// Leave the invalid bytes out
+    Skip,
+    /// Stop at the invalid bytes and report them
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Chars,
+    Graphemes,
+}
+
+/// Prints the first `num` units of `file`. With [`OnInvalid::Error`],
+/// invalid UTF-8 before the last of them is an `InvalidData` error giving
+/// its byte offset.
+pub fn print_first(
+    file: &mut impl BufRead,
+    num: u64,
+    unit: Unit,
+    on_invalid: OnInvalid,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut printer = Printer { unit, left: num, text: String::new() };
+    // Bytes read but not yet decoded, which start at `offset` in the file.
+    let mut pending = Vec::new();
+    let mut offset = 0;
+    while printer.left > 0 {
+        let buf = file.fill_buf()?;
+        let eof = buf.is_empty();
+        pending.extend_from_slice(buf);
+        let len = buf.len();
+        file.consume(len);
+
+        let mut at = 0;
+        while at < pending.len() {
+            let err = match str::from_utf8(&pending[at..]) {
+                Ok(text) => {
+                    printer.text.push_str(text);
+                    at = pending.len();
+                    break;
+                }
+                Err(err) => err,
+            };
+            let valid = at + err.valid_up_to();
+            // The checked prefix is valid UTF-8.
+            printer.text.push_str(str::from_utf8(&pending[at..valid]).unwrap_or_default());
+            let bad = match err.error_len() {
+                // The rest may be completed by the next read.
+                None if !eof => {
+                    at = valid;
+                    break;
+                }
+                None => valid..pending.len(),
+                Some(bad_len) => valid..valid + bad_len,
+            };
+            at = bad.end;
+
+            match on_invalid {
+                OnInvalid::Replace => printer.text.push(char::REPLACEMENT_CHARACTER),
+                OnInvalid::Skip => {}
+                OnInvalid::Keep => {
+                    printer.flush(true, out)?;
+                    if printer.left > 0 {
+                        out.write_all(&pending[bad])?;
+                        printer.left -= 1;
+                    }
+                }
+                OnInvalid::Error => {
+                    printer.flush(true, out)?;
+                    if printer.left > 0 {
+                        let message =
+                            format!("invalid UTF-8 at byte {}", offset + bad.start as u64);
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                    }
+                }
+            }
+        }
+        printer.flush(eof, out)?;
+        offset += at as u64;
+        pending.drain(..at);
+        if eof {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Decoded text on its way out, and how many units are still to be printed.
+struct Printer {
+    unit: Unit,
+    left: u64,
+    text: String,
+}
+
+impl Printer {
+    /// Prints what it can of the text. Until `last`, the final grapheme
+    /// cluster is held back, as more combining marks may follow.
+    fn flush(&mut self, last: bool, out: &mut impl Write) -> io::Result<()> {
+        let mut end = 0;
+        match self.unit {
+            Unit::Chars => {
+                for (i, c) in self.text.char_indices() {
+                    if self.left == 0 {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    self.left -= 1;
+                }
+            }
+            Unit::Graphemes => {
+                let mut clusters = self.text.grapheme_indices(true).peekable();
+                while let Some((i, cluster)) = clusters.next() {
+                    if self.left == 0 || (!last && clusters.peek().is_none()) {
+                        break;
+                    }
+                    end = i + cluster.len();
+                    self.left -= 1;
+                }
+            }
+        }
+        out.write_all(&self.text.as_bytes()[..end])?;
+        self.text.drain(..end);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first(input: &[u8], num: u64, unit: Unit, on_invalid: OnInvalid) -> io::Result<Vec<u8>> {
+        // A one-byte buffer splits every sequence across reads.
+        let mut file = io::BufReader::with_capacity(1, input);
+        let mut out = Vec::new();
+        print_first(&mut file, num, unit, on_invalid, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_chars() {
+        let text = "诚邀您参加".as_bytes();
+        assert_eq!(first(text, 2, Unit::Chars, OnInvalid::Replace).unwrap(), "诚邀".as_bytes());
+        assert_eq!(first(text, 9, Unit::Chars, OnInvalid::Replace).unwrap(), text);
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let text = "e\u{301}x\u{1F1EB}\u{1F1F7}!".as_bytes();
+        let out = first(text, 3, Unit::Graphemes, OnInvalid::Replace).unwrap();
+        assert_eq!(out, "e\u{301}x\u{1F1EB}\u{1F1F7}".as_bytes());
+        let out = first(text, 1, Unit::Chars, OnInvalid::Replace).unwrap();
+        assert_eq!(out, b"e");
+    }
+
+    #[test]
+    fn test_on_invalid() {
+        let text = b"a\xffb\xe8\xaf";
+        let out = first(text, 9, Unit::Chars, OnInvalid::Replace).unwrap();
+        assert_eq!(out, "a\u{FFFD}b\u{FFFD}".as_bytes());
+        assert_eq!(first(text, 3, Unit::Chars, OnInvalid::Keep).unwrap(), b"a\xffb");
+        assert_eq!(first(text, 9, Unit::Chars, OnInvalid::Keep).unwrap(), text);
+        assert_eq!(first(text, 2, Unit::Chars, OnInvalid::Skip).unwrap(), b"ab");
+        assert_eq!(first(text, 1, Unit::Chars, OnInvalid::Error).unwrap(), b"a");
+
+        let err = first(text, 2, Unit::Chars, OnInvalid::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "invalid UTF-8 at byte 1");
+    }
+}
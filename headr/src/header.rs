@@ -0,0 +1,127 @@
+//! `--header-format`: the header printed before each file, with
+//! placeholders such as `{name}` filled in.
+
+use std::fs;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+
+pub const DEFAULT: &str = "==> {name} <==";
+
+const PLACEHOLDERS: &str = "{name}, {size}, {mtime}, {lines_shown}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Name,
+    Size,
+    Mtime,
+    LinesShown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderFormat(Vec<Piece>);
+
+/// Parses a header template. `{{` and `}}` stand for literal braces.
+pub fn parse(value: &str) -> Result<HeaderFormat, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut rest = value;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(c);
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '}' {
+            return Err("unmatched '}' (use '}}' for a literal brace)".to_string());
+        }
+        if c != '{' {
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let end = rest
+            .find('}')
+            .ok_or_else(|| "unmatched '{' (use '{{' for a literal brace)".to_string())?;
+        let piece = match &rest[1..end] {
+            "name" => Piece::Name,
+            "size" => Piece::Size,
+            "mtime" => Piece::Mtime,
+            "lines_shown" => Piece::LinesShown,
+            other => {
+                return Err(format!(
+                    "unknown placeholder '{{{}}}' (expected one of {})",
+                    other, PLACEHOLDERS
+                ))
+            }
+        };
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pieces.push(piece);
+        rest = &rest[end + 1..];
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(HeaderFormat(pieces))
+}
+
+impl HeaderFormat {
+    /// Whether the file has to be read before its header can be printed.
+    pub fn needs_lines_shown(&self) -> bool {
+        self.0.contains(&Piece::LinesShown)
+    }
+
+    /// The header for `filename`. The size and modification time of
+    /// standard input, or of a file that cannot be examined, show as `-`.
+    pub fn render(&self, filename: &str, lines_shown: Option<usize>) -> String {
+        let meta = match filename {
+            "-" => None,
+            _ => fs::metadata(filename).ok(),
+        };
+        let mut header = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => header.push_str(text),
+                Piece::Name => header.push_str(filename),
+                Piece::Size => header.push_str(
+                    &meta.as_ref().map_or("-".to_string(), |meta| meta.len().to_string()),
+                ),
+                Piece::Mtime => header.push_str(
+                    &meta
+                        .as_ref()
+                        .and_then(|meta| meta.modified().ok())
+                        .map_or("-".to_string(), format_time),
+                ),
+                Piece::LinesShown => header.push_str(
+                    &lines_shown.map_or("-".to_string(), |lines| lines.to_string()),
+                ),
+            }
+        }
+        header
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let format = parse("{{{name}}} has {lines_shown}").unwrap();
+        assert_eq!(format.render("f", Some(3)), "{f} has 3");
+        assert!(format.needs_lines_shown());
+        assert!(!parse(DEFAULT).unwrap().needs_lines_shown());
+
+        assert!(parse("{nope}").unwrap_err().contains("unknown placeholder '{nope}'"));
+        assert!(parse("{name").unwrap_err().contains("unmatched '{'"));
+        assert!(parse("name}").unwrap_err().contains("unmatched '}'"));
+    }
+}
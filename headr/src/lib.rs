@@ -1,13 +1,28 @@
 use std::{
-    fs::File, 
-    io::{self, BufReader, BufRead}
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufReader, BufRead, BufWriter, Read, Seek, Write}
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 
+mod chars;
+mod header;
+
+use chars::{OnInvalid, Unit};
+use header::HeaderFormat;
+
 #[derive(Debug, Parser)]
-#[command(about, version, author)]
+#[command(
+    about,
+    version,
+    author,
+    after_help("NUM may have a multiplier suffix:
+b 512, kB 1000, K 1024, MB 1000*1000, M 1024*1024,
+GB 1000*1000*1000, G 1024*1024*1024, and so on for T, P, E, Z, Y.
+Binary prefixes can be used, too: KiB=K, MiB=M, and so on.")
+)]
 pub struct Args {
     #[arg(default_value = "-")]
     files: Vec<String>,
@@ -20,55 +35,410 @@ pub struct Args {
         NUM lines of each file"),
         conflicts_with("bytes"),
         default_value("10"),
-        value_parser(clap::value_parser!(u64).range(1..))
+        allow_hyphen_values(true),
+        value_parser(parse_lines)
     )]
-    lines: u64,
+    lines: Count,
 
     #[arg(
         short('c'),
         long,
-        help("print the last NUM bytes of each file;
+        help("print the first NUM bytes of each file;
         with the leading '-', print all but the last
         NUM bytes of each file"),
-        value_parser = clap::value_parser!(u64).range(1..),
+        allow_hyphen_values(true),
+        value_parser(parse_bytes)
+    )]
+    bytes: Option<Count>,
+
+    #[arg(
+        long,
+        value_name("NUM"),
+        help("print the first NUM characters of each file,
+        never splitting a multibyte one"),
+        conflicts_with_all(["lines", "bytes", "graphemes"]),
+        value_parser(parse_chars)
+    )]
+    chars: Option<u64>,
+
+    #[arg(
+        long,
+        value_name("NUM"),
+        help("print the first NUM grapheme clusters of each file"),
+        conflicts_with_all(["lines", "bytes"]),
+        value_parser(parse_graphemes)
+    )]
+    graphemes: Option<u64>,
+
+    #[arg(
+        long,
+        value_name("POLICY"),
+        help("what --chars and --graphemes do with invalid UTF-8"),
+        value_enum,
+        default_value("replace")
+    )]
+    on_invalid: OnInvalid,
+
+    #[arg(
+        short('q'),
+        long,
+        visible_alias("silent"),
+        help("never print headers giving file names"),
+        overrides_with("verbose")
+    )]
+    quiet: bool,
+
+    #[arg(
+        short('v'),
+        long,
+        help("always print headers giving file names"),
+        overrides_with("quiet")
+    )]
+    verbose: bool,
+
+    #[arg(
+        long,
+        value_name("FORMAT"),
+        help("the header printed before each file, with the placeholders
+        {name}, {size}, {mtime} and {lines_shown}"),
+        default_value(header::DEFAULT),
+        allow_hyphen_values(true),
+        value_parser(header::parse)
+    )]
+    header_format: HeaderFormat,
+
+    #[arg(
+        short('z'),
+        long,
+        help("line delimiter is NUL, not newline")
     )]
-    bytes: Option<u64>,
+    zero_terminated: bool,
+}
+
+impl Args {
+    fn headers(&self) -> bool {
+        match (self.quiet, self.verbose) {
+            (true, _) => false,
+            (_, true) => true,
+            _ => self.files.len() > 1,
+        }
+    }
+
+    /// The `--chars` or `--graphemes` count, if one was given.
+    fn units(&self) -> Option<(u64, Unit)> {
+        match (self.chars, self.graphemes) {
+            (Some(num), _) => Some((num, Unit::Chars)),
+            (_, Some(num)) => Some((num, Unit::Graphemes)),
+            _ => None,
+        }
+    }
+
+    fn delimiter(&self) -> u8 {
+        if self.zero_terminated { b'\0' } else { b'\n' }
+    }
+}
+
+/// How much of each file to print: the first NUM lines (or bytes), or all
+/// but the last NUM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    First(u64),
+    AllButLast(u64),
+}
+
+fn parse_lines(value: &str) -> Result<Count, String> {
+    parse_count(value, "lines")
+}
+
+fn parse_bytes(value: &str) -> Result<Count, String> {
+    parse_count(value, "bytes")
+}
+
+/// Parses NUM as GNU head does: an optional leading '-', then a number with
+/// an optional multiplier suffix such as `K` or `MB`.
+fn parse_count(value: &str, what: &str) -> Result<Count, String> {
+    let (all_but_last, num) = match value.strip_prefix('-') {
+        Some(num) => (true, num),
+        None => (false, value),
+    };
+    let num = parse_size(num).map_err(|err| err.message(what, num))?;
+    match (all_but_last, num) {
+        (true, num) => Ok(Count::AllButLast(num)),
+        (false, 0) => Err(format!("0 is not in 1..={}", u64::MAX)),
+        (false, num) => Ok(Count::First(num)),
+    }
+}
+
+fn parse_chars(value: &str) -> Result<u64, String> {
+    parse_size(value).map_err(|err| err.message("characters", value))
+}
+
+fn parse_graphemes(value: &str) -> Result<u64, String> {
+    parse_size(value).map_err(|err| err.message("grapheme clusters", value))
+}
+
+enum SizeError {
+    Invalid,
+    TooLarge,
+}
+
+impl SizeError {
+    fn message(&self, what: &str, num: &str) -> String {
+        match self {
+            SizeError::Invalid => format!("invalid number of {}: '{}'", what, num),
+            SizeError::TooLarge => format!(
+                "invalid number of {}: '{}': Value too large for defined data type",
+                what, num
+            ),
+        }
+    }
+}
+
+/// Parses a number with an optional suffix: `b` (512), `K`/`KiB` (1024),
+/// `KB` (1000), `M`/`MiB`, `MB`, and so on through `G`, `T`, `P`, `E`, `Z`
+/// and `Y`.
+fn parse_size(value: &str) -> Result<u64, SizeError> {
+    let value = value.trim_start();
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    if digits == 0 {
+        return Err(SizeError::Invalid);
+    }
+    let (digits, suffix) = value.split_at(digits);
+
+    let (base, power, rest): (u64, u32, _) = match suffix.chars().next() {
+        None => (1, 0, suffix),
+        Some('b') => (512, 1, &suffix[1..]),
+        Some(unit) => {
+            let power = match unit {
+                'k' | 'K' => 1,
+                'm' | 'M' => 2,
+                'G' => 3,
+                'T' => 4,
+                'P' => 5,
+                'E' => 6,
+                'Z' => 7,
+                'Y' => 8,
+                _ => return Err(SizeError::Invalid),
+            };
+            match &suffix[1..] {
+                rest if rest.starts_with("iB") => (1024, power, &rest[2..]),
+                rest if rest.starts_with(['B', 'D']) => (1000, power, &rest[1..]),
+                rest => (1024, power, rest),
+            }
+        }
+    };
+    if !rest.is_empty() {
+        return Err(SizeError::Invalid);
+    }
+    let unit = base.checked_pow(power).ok_or(SizeError::TooLarge)?;
+    let num: u64 = digits.parse().map_err(|_| SizeError::TooLarge)?;
+    num.checked_mul(unit).ok_or(SizeError::TooLarge)
 }
 
 pub fn run(args: Args) -> Result<()> {
-    for (file_num, filename) in args.files.iter().enumerate() {
-        if args.files.len() > 1 {
-            println!("==> {} <==", &filename);
+    let mut out = io::stdout();
+    let mut first = true;
+    let mut invalid = 0;
+    for filename in &args.files {
+        let mut file = match open(filename) {
+            Err(err) => {
+                eprintln!("{}: {}", filename, err);
+                continue;
+            }
+            Ok(file) => file,
+        };
+        if !args.headers() {
+            invalid += print_head(&args, filename, &mut file, &mut out)?;
+            continue;
+        }
+
+        if !first {
+            writeln!(out)?;
+        }
+        first = false;
+        if args.header_format.needs_lines_shown() {
+            // The count goes in the header, so the output is spilled to a
+            // temporary file rather than held in memory.
+            let spill = BufWriter::new(tempfile::tempfile()?);
+            let mut body = LineCounter::new(spill, args.delimiter());
+            invalid += print_head(&args, filename, &mut file, &mut body)?;
+            let lines = body.lines();
+            let mut spill = body.inner.into_inner().map_err(|err| err.into_error())?;
+            spill.rewind()?;
+
+            let header = args.header_format.render(filename, Some(lines));
+            writeln!(out, "{}", header)?;
+            io::copy(&mut spill, &mut out)?;
+        } else {
+            writeln!(out, "{}", args.header_format.render(filename, None))?;
+            invalid += print_head(&args, filename, &mut file, &mut out)?;
         }
-        let ending = if file_num < args.files.len() - 1 { "\n".to_string() } 
-                                    else { "".to_string() };
-        match open(&filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(mut file) => {
-                match args.bytes {
-                    Some(bytes) => {
-                        let mut buf = vec![0; bytes as usize];
-                        let bytes_read = file.read(&mut buf)?;
-                        print!("{}{}", 
-                            String::from_utf8_lossy(&buf[..bytes_read]),
-                            ending,
-                        );
-                    },
-                    _ => {
-                        for line in file.lines().take(args.lines as usize) {
-                            println!("{}", line?);
-                        }
-                        if file_num < args.files.len() - 1 {
-                            print!("{}", ending);
-                        }
-                    } 
-                }
+    }
+    match invalid {
+        0 => Ok(()),
+        1 => bail!("invalid UTF-8 found in 1 file"),
+        _ => bail!("invalid UTF-8 found in {} files", invalid),
+    }
+}
+
+/// Prints the part of one file that was asked for. Returns 1 if the file
+/// stopped at invalid UTF-8 (with `--on-invalid error`), which has been
+/// reported, and 0 otherwise.
+fn print_head(
+    args: &Args,
+    filename: &str,
+    file: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<usize> {
+    if let Some((num, unit)) = args.units() {
+        return match chars::print_first(file, num, unit, args.on_invalid, out) {
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("{}: {}", filename, err);
+                Ok(1)
             }
+            printed => printed.map(|_| 0).map_err(Into::into),
+        };
+    }
+    match args.bytes {
+        Some(Count::AllButLast(num)) => match regular_file_len(filename) {
+            Some(len) => {
+                io::copy(&mut file.take(len.saturating_sub(num)), out)?;
+            }
+            None => print_all_but_last_bytes(file, num, out)?,
+        },
+        Some(Count::First(bytes)) => {
+            io::copy(&mut file.take(bytes), out)?;
         }
+        None => match args.lines {
+            Count::First(num) => print_first_lines(file, num, args.delimiter(), out)?,
+            Count::AllButLast(num) => {
+                print_all_but_last_lines(file, num, args.delimiter(), out)?;
+            }
+        },
+    }
+    Ok(0)
+}
+
+/// Counts the lines written through it.
+struct LineCounter<W> {
+    inner: W,
+    delimiter: u8,
+    delimiters: usize,
+    /// Whether the output so far ends with a delimiter (or is empty).
+    ended: bool,
+}
+
+impl<W: Write> LineCounter<W> {
+    fn new(inner: W, delimiter: u8) -> Self {
+        LineCounter { inner, delimiter, delimiters: 0, ended: true }
+    }
+
+    /// The lines written, counting a last line without a delimiter.
+    fn lines(&self) -> usize {
+        self.delimiters + usize::from(!self.ended)
+    }
+}
+
+impl<W: Write> Write for LineCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let written = &buf[..n];
+        self.delimiters += written.iter().filter(|&&b| b == self.delimiter).count();
+        if let Some(&last) = written.last() {
+            self.ended = last == self.delimiter;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Prints the first `num` lines as they are, delimiters included.
+fn print_first_lines(
+    file: &mut impl BufRead,
+    num: u64,
+    delimiter: u8,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut line = Vec::new();
+    for _ in 0..num {
+        line.clear();
+        if file.read_until(delimiter, &mut line)? == 0 {
+            break;
+        }
+        out.write_all(&line)?;
     }
     Ok(())
 }
 
+/// Prints all lines but the last `num`, holding back at most `num` lines.
+fn print_all_but_last_lines(
+    file: &mut impl BufRead,
+    num: u64,
+    delimiter: u8,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut held = VecDeque::new();
+    let mut line = Vec::new();
+    while file.read_until(delimiter, &mut line)? > 0 {
+        if num == 0 {
+            out.write_all(&line)?;
+            line.clear();
+            continue;
+        }
+        held.push_back(line);
+        line = Vec::new();
+        if held.len() as u64 > num {
+            if let Some(mut oldest) = held.pop_front() {
+                out.write_all(&oldest)?;
+                oldest.clear();
+                line = oldest;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints all bytes but the last `num`, holding back at most `num` bytes
+/// (and one read's worth).
+fn print_all_but_last_bytes(
+    file: &mut impl BufRead,
+    num: u64,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut held = VecDeque::new();
+    loop {
+        let buf = file.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        held.extend(buf);
+        let n = buf.len();
+        file.consume(n);
+
+        let excess = (held.len() as u64).saturating_sub(num) as usize;
+        if excess > 0 {
+            let (front, back) = held.as_slices();
+            let from_front = excess.min(front.len());
+            out.write_all(&front[..from_front])?;
+            out.write_all(&back[..excess - from_front])?;
+            held.drain(..excess);
+        }
+    }
+}
+
+/// The size of a named regular file, which tells how much to print without
+/// reading it twice; pipes and the like have to be streamed.
+fn regular_file_len(filename: &str) -> Option<u64> {
+    match filename {
+        "-" => None,
+        _ => fs::metadata(filename).ok().filter(|meta| meta.is_file()).map(|meta| meta.len()),
+    }
+}
+
 pub fn open(filename: &str) -> Result<Box<dyn std::io::BufRead>> {
     match filename {
         "-" => Ok(
@@ -32,12 +32,8 @@ fn gen_bad_filename() -> String {
 }
 
 fn run(args: &[&str], expected: &str) -> Result<()> {
-    // Warnings: read_to_string will fail when split utf8
-    // let expected = std::fs::read_to_string(expected)?;
-    let mut buf: Vec<u8> = Vec::new();
-    headr::open(expected)?
-        .read_to_end(&mut buf);
-    let expected = String::from_utf8_lossy(&buf);
+    // Compare bytes: output cut mid-character is not valid UTF-8
+    let expected = std::fs::read(expected)?;
 
     let output = Command::cargo_bin(PRG)?
         .args(args)
@@ -45,10 +41,7 @@ fn run(args: &[&str], expected: &str) -> Result<()> {
 
     // dbg!(&output);
     assert!(&output.status.success());
-    assert_eq!(
-        String::from_utf8_lossy(&output.stdout), 
-        expected,
-    );
+    assert_eq!(output.stdout, expected);
 
     Ok(())
 }
@@ -58,23 +51,15 @@ fn run_stdin(
     input: &str, 
     expected: &str,
 ) -> Result<()> {
-    // let expected = std::fs::read_to_string(expected)?;
-    let mut buf: Vec<u8> = Vec::new();
-    headr::open(expected)?
-        .read_to_end(&mut buf);
-    let expected = String::from_utf8_lossy(&buf);
-
-    let input = std::fs::read_to_string(input)?;
+    let expected = std::fs::read(expected)?;
+    let input = std::fs::read(input)?;
 
     let output = Command::cargo_bin(PRG)?
         .args(args)
         .write_stdin(input)
         .output()?;
     assert!(output.status.success());
-    assert_eq!(
-        String::from_utf8_lossy(&output.stdout), 
-        expected,
-    );
+    assert_eq!(output.stdout, expected);
     Ok(())
 }
 
@@ -457,3 +442,329 @@ fn cn() -> Result<()> {
         "tests/expected/cn.txt.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn empty_n_minus2() -> Result<()> {
+    run(&[EMPTY, "-n", "-2"], "tests/expected/empty.txt.n-2.out")
+}
+
+#[test]
+fn one_n_minus2() -> Result<()> {
+    run(&[ONE, "-n", "-2"], "tests/expected/one.txt.n-2.out")
+}
+
+#[test]
+fn three_n_minus2() -> Result<()> {
+    run(&[THREE, "-n-2"], "tests/expected/three.txt.n-2.out")
+}
+
+#[test]
+fn twelve_n_minus2() -> Result<()> {
+    run(&[TWELVE, "-n", "-2"], "tests/expected/twelve.txt.n-2.out")
+}
+
+#[test]
+fn twelve_n_minus2_stdin() -> Result<()> {
+    run_stdin(&["-n", "-2"], TWELVE, "tests/expected/twelve.txt.n-2.out")
+}
+
+#[test]
+fn cn_n_minus2() -> Result<()> {
+    run(&[CN, "-n", "-2"], "tests/expected/cn.txt.n-2.out")
+}
+
+#[test]
+fn multiple_files_n_minus4() -> Result<()> {
+    run(
+        &["-n", "-4", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n-4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_c_minus5() -> Result<()> {
+    run(&[TWELVE, "-c", "-5"], "tests/expected/twelve.txt.c-5.out")
+}
+
+#[test]
+fn twelve_c_minus5_stdin() -> Result<()> {
+    run_stdin(&["-c", "-5"], TWELVE, "tests/expected/twelve.txt.c-5.out")
+}
+
+#[test]
+fn multiple_files_c_minus3() -> Result<()> {
+    run(
+        &["-c", "-3", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.c-3.out",
+    )
+}
+
+#[test]
+fn cn_c_minus4_is_byte_exact() -> Result<()> {
+    let expected = std::fs::read("tests/expected/cn.txt.c-4.out")?;
+
+    let output = Command::cargo_bin(PRG)?.args(["-c", "-4", CN]).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["-c", "-4"])
+        .write_stdin(std::fs::read(CN)?)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn stdin_bytes_len(args: &[&str], len: usize) -> Result<usize> {
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(vec![b'x'; len])
+        .output()?;
+    assert!(output.status.success());
+    Ok(output.stdout.len())
+}
+
+#[test]
+fn size_suffixes() -> Result<()> {
+    assert_eq!(stdin_bytes_len(&["-c", "1b"], 5000)?, 512);
+    assert_eq!(stdin_bytes_len(&["-c", "1K"], 5000)?, 1024);
+    assert_eq!(stdin_bytes_len(&["-c", "1KiB"], 5000)?, 1024);
+    assert_eq!(stdin_bytes_len(&["-c", "2KB"], 5000)?, 2000);
+    assert_eq!(stdin_bytes_len(&["-c", "-2b"], 5000)?, 3976);
+    assert_eq!(stdin_bytes_len(&["-c", "1MB"], 5000)?, 5000);
+    Ok(())
+}
+
+#[test]
+fn twelve_n_suffix() -> Result<()> {
+    run(&[TWELVE, "-n", "1K"], TWELVE)
+}
+
+#[test]
+fn bad_suffix() -> Result<()> {
+    for (flag, what) in [("-c", "bytes"), ("-n", "lines")] {
+        for bad in ["10X", "1B", "1Ki", "1.5K", "-2X"] {
+            let num = bad.strip_prefix('-').unwrap_or(bad);
+            Command::cargo_bin(PRG)?
+                .args([flag, bad, EMPTY])
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains(format!(
+                    "invalid number of {}: '{}'\n",
+                    what, num
+                )));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn suffix_overflow() -> Result<()> {
+    for bad in ["16E", "1Z", "18446744073709551616", "-99999E"] {
+        let num = bad.strip_prefix('-').unwrap_or(bad);
+        Command::cargo_bin(PRG)?
+            .args(["-c", bad, EMPTY])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "invalid number of bytes: '{}': Value too large for defined data type",
+                num
+            )));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose() -> Result<()> {
+    run(&["-v", ONE], "tests/expected/one.txt.v.out")
+}
+
+#[test]
+fn multiple_files_quiet() -> Result<()> {
+    run(
+        &["-q", "-n", "2", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n2.q.out",
+    )
+}
+
+#[test]
+fn quiet_and_verbose_last_wins() -> Result<()> {
+    run(&["-q", "-v", ONE], "tests/expected/one.txt.v.out")?;
+    run(&["-v", "-q", ONE], ONE)
+}
+
+#[test]
+fn header_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "2", "--header-format", "-- {name} {size} {lines_shown} --", ONE, TWELVE])
+        .assert()
+        .success()
+        .stdout(format!(
+            "-- {ONE} 23 1 --\nÖne line, four words.\n\n-- {TWELVE} 63 2 --\none\ntwo\n"
+        ));
+    Ok(())
+}
+
+#[test]
+fn header_format_lines_shown_all_but_last() -> Result<()> {
+    let twelve = std::fs::read_to_string(TWELVE)?;
+    let shown: String = twelve.split_inclusive('\n').take(9).collect();
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-n", "-3", "--header-format", "{name}: {lines_shown}"])
+        .write_stdin(twelve)
+        .assert()
+        .success()
+        .stdout(format!("-: 9\n{shown}"));
+    Ok(())
+}
+
+#[test]
+fn header_format_mtime_and_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-c", "3", "--header-format", "{name} {size} {mtime}"])
+        .write_stdin("abcdef")
+        .assert()
+        .success()
+        .stdout("- - -\nabc");
+    Command::cargo_bin(PRG)?
+        .args(["-v", "--header-format", "{mtime}", EMPTY])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^\d{4}-\d\d-\d\d \d\d:\d\d:\d\d\n$")?);
+    Ok(())
+}
+
+#[test]
+fn bad_header_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--header-format", "{nope}", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown placeholder '{nope}'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+const ZERO: &str = "./tests/inputs/zero.txt";
+
+fn run_bytes(args: &[&str], input: &str, expected: &str) -> Result<()> {
+    let expected = std::fs::read(expected)?;
+
+    let output = Command::cargo_bin(PRG)?.args(args).arg(input).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(std::fs::read(input)?)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+#[test]
+fn zero_terminated_n2() -> Result<()> {
+    run_bytes(&["-z", "-n", "2"], ZERO, "tests/expected/zero.txt.z.n2.out")
+}
+
+#[test]
+fn zero_terminated_n_minus2() -> Result<()> {
+    run_bytes(&["-z", "-n", "-2"], ZERO, "tests/expected/zero.txt.z.n-2.out")
+}
+
+#[test]
+fn zero_terminated_lines_shown() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-v", "-n", "3", "--header-format", "{lines_shown}", ZERO])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("3\none\0"));
+    Ok(())
+}
+
+// --------------------------------------------------
+const CRLF: &str = "./tests/inputs/crlf.txt";
+
+#[test]
+fn crlf_n2_is_byte_exact() -> Result<()> {
+    run_bytes(&["-n", "2"], CRLF, "tests/expected/crlf.txt.n2.out")
+}
+
+#[test]
+fn crlf_no_newline_added() -> Result<()> {
+    run_bytes(&["-n", "10"], CRLF, CRLF)
+}
+
+#[test]
+fn c_reads_all_of_a_pipe() -> Result<()> {
+    let input: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+    let output = Command::cargo_bin(PRG)?
+        .args(["-c", "200000"])
+        .write_stdin(input.clone())
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, &input[..200_000]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn cn_chars5() -> Result<()> {
+    run_bytes(&["--chars", "5"], CN, "tests/expected/cn.txt.chars5.out")
+}
+
+#[test]
+fn graphemes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--graphemes", "3"])
+        .write_stdin("e\u{301}x\u{1F1EB}\u{1F1F7}!")
+        .assert()
+        .success()
+        .stdout("e\u{301}x\u{1F1EB}\u{1F1F7}");
+    Ok(())
+}
+
+#[test]
+fn chars_on_invalid() -> Result<()> {
+    for (policy, expected) in [
+        ("replace", "ab\u{FFFD}".as_bytes()),
+        ("keep", b"ab\xff"),
+        ("skip", b"abc"),
+    ] {
+        let output = Command::cargo_bin(PRG)?
+            .args(["--chars", "3", "--on-invalid", policy])
+            .write_stdin(&b"ab\xffcd"[..])
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(output.stdout, expected);
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "3", "--on-invalid", "error", "-", CN])
+        .write_stdin(&b"ab\xffcd"[..])
+        .assert()
+        .failure()
+        .stdout(format!("==> - <==\nab\n==> {CN} <==\n诚邀\n"))
+        .stderr("-: invalid UTF-8 at byte 2\nerror: invalid UTF-8 found in 1 file\n");
+    Ok(())
+}
+
+#[test]
+fn chars_conflicts() -> Result<()> {
+    for args in [["--chars", "1", "-c", "1"], ["--graphemes", "1", "-n", "1"]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
@@ -19,7 +19,7 @@ pub fn run(mut args: Args) -> Result<()> {
     // let num_file = &args.files.len();
 
     for filename in &args.files {
-        let file = open(&filename)?;
+        let file = open(filename)?;
         if let Ok(info) = count(file) {
             // println!("{} {} {} {}", info.num_lines, info.num_words, info.num_bytes, filename);
             // println!("{:>8} {:>8} {:>8} {}", info.num_lines, info.num_words, info.num_bytes, filename);
@@ -43,12 +43,11 @@ pub fn run(mut args: Args) -> Result<()> {
     }
 
     if args.files.len() > 1usize {
-        println!("{}{}{}{}{}",
+        println!("{}{}{}{} total",
             showable(total_lines, args.lines),
             showable(total_words, args.words),
             showable(total_bytes, args.bytes),
             showable(total_chars, args.chars),
-            " total",
         );
     }    
 
@@ -55,7 +55,7 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
 fn skips_bad_file() -> Result<()> {
     let bad = gen_bad_file();
     // let expected = format!("{bad}: .* [(]os error 2[)]");
-    let expected = format!(".* [(]os error 2[)]");
+    let expected = ".* [(]os error 2[)]".to_string();
 
     Command::cargo_bin(PRG)?
         .arg(bad)